@@ -1,28 +1,216 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::anychar;
-use nom::combinator::{iterator, value};
+use nom::combinator::{fail, iterator, value};
 use nom::IResult;
 
-pub mod part1 {
-    use super::*;
+/// How a digit was written in the line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    /// A literal digit, e.g. `'2'`
+    Digit,
+    /// A spelled-out number word, e.g. `"two"`
+    Word,
+}
 
-    pub fn run(input: &str) -> Result<String> {
-        let total = input.lines().fold(0, |acc, line| {
-            let mut chars = line.chars();
+/// A digit recognised in a line.
+#[derive(Debug, PartialEq)]
+pub struct Token {
+    pub value: u32,
+    pub kind: TokenKind,
+    /// The byte offset in the line where the digit (or number word) starts
+    pub offset: usize,
+}
 
-            // Find the first number in the line
-            let first = chars.find_map(|c| c.to_digit(10)).unwrap();
+impl Token {
+    /// The byte offset in the line just past the digit (or number word).
+    fn end(&self) -> usize {
+        match self.kind {
+            TokenKind::Digit => self.offset + 1,
+            TokenKind::Word => {
+                self.offset + NUMBER_WORDS[self.value as usize - 1].len()
+            }
+        }
+    }
+}
+
+/// Spelled-out number words, from "one" (1) to "nine" (9).
+const NUMBER_WORDS: [&str; 9] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Runs the chosen part over each input, returning each input's sum of
+/// calibration values. A failing input doesn't stop the rest from running.
+pub fn run_many(inputs: &[&str], part2: bool) -> Vec<Result<u64>> {
+    inputs
+        .iter()
+        .map(|input| calibration_total(input, part2))
+        .collect()
+}
 
-            // Find the last (first from the back) number in the line
-            let last = chars
-                .rfind(|c| c.is_ascii_digit())
-                .map(|c| c.to_digit(10).unwrap())
-                .unwrap_or(first);
+/// Sums up every line's calibration value, counting number words as digits
+/// if `words` is set.
+///
+/// Errors on the first line that has no digits at all.
+fn calibration_total(input: &str, words: bool) -> Result<u64> {
+    Ok(line_contributions(input, words)?
+        .iter()
+        .map(|&value| value as u64)
+        .sum())
+}
 
-            acc + (first * 10) + last
-        });
+/// Lists every line's calibration value (the two-digit number it adds to the
+/// total), counting number words as digits if `part2` is set.
+///
+/// Errors on the first line that has no digits at all.
+pub fn line_contributions(input: &str, part2: bool) -> Result<Vec<u32>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let value = if part2 {
+                part2::parse_line(line).ok().map(|(_, value)| value)
+            } else {
+                let mut digits = line.chars().filter_map(|c| c.to_digit(10));
+                digits.next().map(|first| {
+                    first * 10 + digits.next_back().unwrap_or(first)
+                })
+            };
+
+            value.with_context(|| {
+                format!("line {} has no digits: {:?}", i + 1, line)
+            })
+        })
+        .collect()
+}
+
+/// Returns the smallest and the largest of the lines' calibration values.
+///
+/// Errors on the first line that has no digits at all, or if there are no
+/// lines.
+pub fn value_extremes(input: &str, part2: bool) -> Result<(u32, u32)> {
+    let values = line_contributions(input, part2)?;
+
+    let min = values.iter().min().context("input has no lines")?;
+    let max = values.iter().max().context("input has no lines")?;
+
+    Ok((*min, *max))
+}
+
+/// Counts the lines whose first or last digit is a number word, like the
+/// `one` in `"one1"`. Such a line's calibration value doesn't necessarily
+/// change between the parts: `"one1"` gives 11 in both.
+pub fn word_usage(input: &str) -> usize {
+    input
+        .lines()
+        .filter(|line| {
+            let tokens = tokens(line);
+            let is_word = |token: Option<&Token>| {
+                token.is_some_and(|token| token.kind == TokenKind::Word)
+            };
+            is_word(tokens.first()) || is_word(tokens.last())
+        })
+        .count()
+}
+
+/// Lists the digits in the line from left to right.
+///
+/// Overlapping number words (e.g. "twone") count as both digits, unless
+/// `strict_overlap` is set, in which case they are rejected as malformed.
+pub fn parse_digits(line: &str, strict_overlap: bool) -> Result<Vec<u32>> {
+    let tokens = tokens(line);
+
+    if strict_overlap {
+        for pair in tokens.windows(2) {
+            if pair[1].offset < pair[0].end() {
+                bail!(
+                    "overlapping number words at offsets {} and {} in {:?}",
+                    pair[0].offset,
+                    pair[1].offset,
+                    line
+                );
+            }
+        }
+    }
+
+    Ok(tokens.iter().map(|token| token.value).collect())
+}
+
+/// Lists every digit and number word in the line from left to right.
+///
+/// Number words may overlap, in which case both are listed. Example:
+/// "twone" -> [Token { 2, Word, 0 }, Token { 1, Word, 2 }]
+pub fn tokens(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut input = line;
+
+    // Keep applying `parse_token` until we get through the end of the line
+    while let Ok((rest, token)) = parse_token(input) {
+        if let Some((value, kind)) = token {
+            let offset = line.len() - input.len();
+            tokens.push(Token {
+                value,
+                kind,
+                offset,
+            });
+        }
+
+        input = rest;
+    }
+
+    tokens
+}
+
+/// Tries to parse some digit from the input string, along with how it was
+/// written.
+///
+/// We first try to parse a "number word" ("one", etc.) from the string.
+///
+/// If successful, that's the digit we need — return a tuple containing
+/// it and the rest of the string (for any further processing).
+///
+/// If not successful, we check if the current char is a digit or not.
+/// If it is, we're done — return the same thing as above. If not, we return
+/// a `None` as the digit (meaning no digit is found).
+fn parse_token(input: &str) -> IResult<&str, Option<(u32, TokenKind)>> {
+    let num_word_parse: IResult<&str, u32> = alt((
+        value(1, tag("one")),
+        value(2, tag("two")),
+        value(3, tag("three")),
+        value(4, tag("four")),
+        value(5, tag("five")),
+        value(6, tag("six")),
+        value(7, tag("seven")),
+        value(8, tag("eight")),
+        value(9, tag("nine")),
+    ))(input);
+
+    // Split the current (first) char from the rest of the string
+    let (rest, char) = anychar(input)?;
+
+    match num_word_parse {
+        // We use the rest of the string from moving by one char here instead
+        // of from the number word because number words may overlap. Example:
+        // "twone" -> [2, 1]
+        // If we used `rest` from `num_word_res`, it would be just `ne`
+        // instead of `wone` (which would've enabled us to catch `one` later)
+        Ok((_, digit)) => Ok((rest, Some((digit, TokenKind::Word)))),
+
+        // Can't parse any number words — that's fine, check if the current
+        // char is a digit or not.
+        Err(_) => Ok((
+            rest,
+            char.to_digit(10).map(|digit| (digit, TokenKind::Digit)),
+        )),
+    }
+}
+
+pub mod part1 {
+    use super::*;
+
+    pub fn run(input: &str) -> Result<String> {
+        let total = calibration_total(input, false)?;
 
         Ok(total.to_string())
     }
@@ -32,16 +220,13 @@ pub mod part2 {
     use super::*;
 
     pub fn run(input: &str) -> Result<String> {
-        let total = input.lines().fold(0, |acc, line| {
-            let (_, number) = parse_line(line).unwrap();
-
-            acc + number
-        });
+        let total = calibration_total(input, true)?;
 
         Ok(total.to_string())
     }
 
-    /// Tries to parse the "calibration value" from the line.
+    /// Tries to parse the "calibration value" from the line, failing if the
+    /// line has no digits.
     pub(super) fn parse_line(line: &str) -> IResult<&str, u32> {
         // Repeatedly apply the `parse_number` parser until we get through the
         // end of the string, collecting only `Some(number)`s.
@@ -54,50 +239,20 @@ pub mod part2 {
         // let (rest, numbers) = many1(parse_number)(line)?;
         // let numbers = numbers.into_iter().flatten().collect::<Vec<_>>();
 
-        let first = numbers.first().unwrap();
-        let last = numbers.last().unwrap_or(first);
+        // A line without any digits has no calibration value
+        let (Some(first), Some(last)) = (numbers.first(), numbers.last())
+        else {
+            return fail(line);
+        };
 
         Ok((rest, first * 10 + last))
     }
 
     /// Tries to parse some digit from the input string.
-    ///
-    /// We first try to parse a "number word" ("one", etc.) from the string.
-    ///
-    /// If successful, that's the digit we need — return a tuple containing
-    /// it and the rest of the string (for any further processing).
-    ///
-    /// If not successful, we check if the current char is a digit or not.
-    /// If it is, we're done — return the same thing as above. If not, we return
-    /// a `None` as the digit (meaning no digit is found).
     fn parse_number(input: &str) -> IResult<&str, Option<u32>> {
-        let num_word_parse: IResult<&str, u32> = alt((
-            value(1, tag("one")),
-            value(2, tag("two")),
-            value(3, tag("three")),
-            value(4, tag("four")),
-            value(5, tag("five")),
-            value(6, tag("six")),
-            value(7, tag("seven")),
-            value(8, tag("eight")),
-            value(9, tag("nine")),
-        ))(input);
-
-        // Split the current (first) char from the rest of the string
-        let (rest, char) = anychar(input)?;
-
-        match num_word_parse {
-            // We use the rest of the string from moving by one char here instead
-            // of from the number word because number words may overlap. Example:
-            // "twone" -> [2, 1]
-            // If we used `rest` from `num_word_res`, it would be just `ne`
-            // instead of `wone` (which would've enabled us to catch `one` later)
-            Ok((_, digit)) => Ok((rest, Some(digit))),
-
-            // Can't parse any number words — that's fine, check if the current
-            // char is a digit or not.
-            Err(_) => Ok((rest, char.to_digit(10))),
-        }
+        let (rest, token) = parse_token(input)?;
+
+        Ok((rest, token.map(|(digit, _)| digit)))
     }
 }
 
@@ -141,4 +296,118 @@ zoneight234
     fn part2_parse_line_ok(#[case] line: &str, #[case] expected: u32) {
         assert_eq!(expected, part2::parse_line(line).unwrap().1);
     }
+
+    #[test]
+    fn tokens_ok() {
+        assert_eq!(
+            vec![
+                Token {
+                    value: 8,
+                    kind: TokenKind::Word,
+                    offset: 0
+                },
+                Token {
+                    value: 2,
+                    kind: TokenKind::Word,
+                    offset: 4
+                },
+                Token {
+                    value: 3,
+                    kind: TokenKind::Word,
+                    offset: 7
+                },
+            ],
+            tokens("eightwothree")
+        );
+    }
+
+    #[test]
+    fn parse_digits_strict_overlap() {
+        assert_eq!(vec![2, 1], parse_digits("twone", false).unwrap());
+        assert!(parse_digits("twone", true).is_err());
+        assert_eq!(vec![2, 1], parse_digits("two1", true).unwrap());
+    }
+
+    #[test]
+    fn run_many_ok() {
+        let part1_input = "1abc2
+pqr3stu8vwx
+a1b2c3d4e5f
+treb7uchet";
+        let part2_input = "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen";
+        let malformed = "1abc2
+pqrstuvwx";
+
+        let results = run_many(&[part1_input, malformed, part2_input], true);
+        assert_eq!(142, *results[0].as_ref().unwrap());
+        assert_eq!(
+            "line 2 has no digits: \"pqrstuvwx\"",
+            results[1].as_ref().unwrap_err().to_string()
+        );
+        assert_eq!(281, *results[2].as_ref().unwrap());
+
+        // Without number words, "eightwothree" has no digits
+        let results = run_many(&[part1_input, part2_input], false);
+        assert_eq!(142, *results[0].as_ref().unwrap());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn part2_digitless_line_err() {
+        let err = part2::run("!!!\ntwo1nine").unwrap_err();
+
+        assert_eq!("line 1 has no digits: \"!!!\"", err.to_string());
+        assert!(part2::parse_line("!!!").is_err());
+    }
+
+    #[test]
+    fn line_contributions_ok() {
+        let input = "1abc2
+pqr3stu8vwx
+a1b2c3d4e5f
+treb7uchet";
+
+        assert_eq!(
+            vec![12, 38, 15, 77],
+            line_contributions(input, false).unwrap()
+        );
+        assert_eq!(
+            vec![12, 38, 15, 77],
+            line_contributions(input, true).unwrap()
+        );
+        assert_eq!(
+            vec![29, 83],
+            line_contributions("two1nine\neightwothree", true).unwrap()
+        );
+    }
+
+    #[test]
+    fn word_usage_ok() {
+        let input = "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen";
+
+        assert_eq!(6, word_usage(input));
+    }
+
+    #[test]
+    fn value_extremes_ok() {
+        let input = "1abc2
+pqr3stu8vwx
+a1b2c3d4e5f
+treb7uchet";
+
+        assert_eq!((12, 77), value_extremes(input, false).unwrap());
+        assert!(value_extremes("", false).is_err());
+    }
 }
@@ -1,85 +1,212 @@
-use anyhow::Result;
+use std::cmp::Reverse;
+use std::io::BufRead;
+
+use anyhow::{anyhow, bail, Context, Result};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{char, u16, u32};
-use nom::combinator::{cut, eof, fail};
+use nom::combinator::{eof, opt};
 use nom::multi::{fold_many1, separated_list1};
-use nom::sequence::{delimited, preceded, separated_pair};
+use nom::sequence::{delimited, pair, separated_pair, terminated};
 use nom::IResult;
 
+/// A set of cubes of each colour.
+#[derive(Debug, Default, PartialEq)]
+pub struct CubeSet {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+}
+
+impl CubeSet {
+    /// The "power" of the set: the numbers of each cube colour multiplied.
+    pub fn power(&self) -> u64 {
+        self.red as u64 * self.green as u64 * self.blue as u64
+    }
+
+    /// Whether there are no more cubes of any colour than in `limits`.
+    pub fn fits_within(&self, limits: &CubeSet) -> bool {
+        self.red <= limits.red
+            && self.green <= limits.green
+            && self.blue <= limits.blue
+    }
+}
+
+/// Whether none of the revealed sets go over `limits`, i.e. the game is
+/// possible. Cubes of the same colour within a set count together.
+fn is_possible(reveals: &[CubeSet], limits: &CubeSet) -> bool {
+    reveals.iter().all(|reveal| reveal.fits_within(limits))
+}
+
+enum Cube {
+    Red(u32),
+    Green(u32),
+    Blue(u32),
+}
+
+/// Parses the `"Game 1: "` prefix of a line into the game's ID.
+fn parse_game_id(input: &str) -> IResult<&str, u16> {
+    delimited(tag("Game "), u16, tag(": "))(input)
+}
+
+/// Solves either part over the games read line-by-line from `reader`, so the
+/// whole input never has to be in memory at once.
+///
+/// For part 1, a game is possible if none of its revealed sets go over
+/// `limits`. Part 2 doesn't use the limits.
+pub fn run_reader<R: BufRead>(
+    reader: R,
+    part2: bool,
+    limits: &CubeSet,
+) -> Result<u64> {
+    reader.lines().try_fold(0, |acc, line| {
+        let line = line?;
+
+        let value = if part2 {
+            let (_, (_, set)) = part2::parse_line(&line)
+                .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+            set.power()
+        } else {
+            let (_, (id, reveals)) = part1::parse_reveals(&line)
+                .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+            if is_possible(&reveals, limits) {
+                id as u64
+            } else {
+                0
+            }
+        };
+
+        Ok(acc + value)
+    })
+}
+
+/// Lists the IDs of the games (in order) with any revealed set going over
+/// `limits`, i.e. the games part 1 leaves out.
+pub fn impossible_game_ids(input: &str, limits: &CubeSet) -> Result<Vec<u16>> {
+    let mut ids = Vec::new();
+    for line in input.lines() {
+        let (_, (id, reveals)) = part1::parse_reveals(line)
+            .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+        if !is_possible(&reveals, limits) {
+            ids.push(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Checks that the games are numbered 1, 2, 3, and so on, in order, without
+/// any gaps or repeats.
+pub fn validate_sequential_ids(input: &str) -> Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let (_, id) = parse_game_id(line).map_err(|e| {
+            anyhow!("line {}: failed to parse game ID: {e}", i + 1)
+        })?;
+
+        // Every ID from 1 up to `expected - 1` has been seen already, so a
+        // lower ID is a repeat (unless it's 0, which is never valid)
+        let expected = i + 1;
+        let id = id as usize;
+        if (1..expected).contains(&id) {
+            bail!("line {}: game ID {} is repeated", i + 1, id);
+        }
+        if id != expected {
+            bail!(
+                "line {}: expected game ID {}, found {}",
+                i + 1,
+                expected,
+                id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the sets of cubes revealed across all games.
+pub fn reveal_count(input: &str) -> Result<usize> {
+    input.lines().try_fold(0, |acc, line| {
+        let (_, (_, reveals)) = part1::parse_reveals(line)
+            .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+        Ok(acc + reveals.len())
+    })
+}
+
 pub mod part1 {
     use super::*;
 
-    const MAX_RED: u16 = 12;
-    const MAX_GREEN: u16 = 13;
-    const MAX_BLUE: u16 = 14;
+    const LIMITS: CubeSet = CubeSet {
+        red: 12,
+        green: 13,
+        blue: 14,
+    };
 
     pub fn run(input: &str) -> Result<String> {
-        let total = input.lines().fold(0, |acc, line| {
-            let (_, id) = parse_line(line).unwrap();
+        let total = input.lines().try_fold(0, |acc, line| -> Result<u64> {
+            let (_, id) = parse_line(line)
+                .map_err(|e| anyhow!("failed to parse game: {e}"))?;
 
-            acc + id.unwrap_or(0)
-        });
+            Ok(acc + id.unwrap_or(0) as u64)
+        })?;
 
         Ok(total.to_string())
     }
 
     /// Outputs the line's game ID if the cube sets are valid, None otherwise.
     pub(super) fn parse_line(line: &str) -> IResult<&str, Option<u16>> {
-        let (rest, id) = parse_game_id(line)?;
+        let (rest, (id, reveals)) = parse_reveals(line)?;
 
-        let id = match parse_game_sets(rest) {
-            Ok(_) => Some(id),
-            Err(_) => None,
-        };
-
-        Ok((rest, id))
+        Ok((rest, is_possible(&reveals, &LIMITS).then_some(id)))
     }
 
-    fn parse_game_id(input: &str) -> IResult<&str, u16> {
-        delimited(tag("Game "), u16, tag(": "))(input)
+    /// Returns the (0-based) index of the first set of cubes revealed in the
+    /// game that goes over any of the `limits`, None if every set is possible
+    /// (or the line can't be parsed).
+    pub fn first_impossible_reveal(
+        line: &str,
+        limits: &CubeSet,
+    ) -> Option<usize> {
+        let (_, (_, reveals)) = parse_reveals(line).ok()?;
+
+        reveals
+            .iter()
+            .position(|reveal| !reveal.fits_within(limits))
     }
 
-    #[derive(Clone)]
-    enum Cube {
-        Red(u16),
-        Green(u16),
-        Blue(u16),
+    /// Parses the line's/game's ID and every set of cubes revealed in it.
+    pub(super) fn parse_reveals(
+        line: &str,
+    ) -> IResult<&str, (u16, Vec<CubeSet>)> {
+        pair(parse_game_id, separated_list1(tag("; "), parse_reveal))(line)
     }
 
-    /// Parses the list of cubes in the given game (input).
+    /// Parses a single set of revealed cubes, whatever their counts.
     ///
-    /// Exits with an error as soon as the first "impossible" cube is found.
-    fn parse_game_sets(input: &str) -> IResult<&str, Vec<Vec<Cube>>> {
-        // "3 blue; 1 red, 2 blue;" -> [[Cube::Blue(3)], [Cube::Red(1), Cube::Blue(2)]]
-        // Notice the `cut(parse_cube)` here! This is what allows short-circuiting
-        // the parsing as soon as an "impossible" cube is found.
-        separated_list1(tag("; "), separated_list1(tag(", "), cut(parse_cube)))(
-            input,
-        )
-    }
-
-    /// Tries to parse an input like "2 red" into `Cube::Red(2)`.
-    ///
-    /// Fails if the input corresponds to an "impossible" cube.
-    fn parse_cube(input: &str) -> IResult<&str, Cube> {
-        // "2 red" -> (2, "red")
-        let (rest, (count, colour)) = separated_pair(
-            u16,
-            char(' '),
-            alt((tag("red"), tag("green"), tag("blue"))),
-        )(input)?;
-
-        // (2, "red") -> `Cube::Red(2)` ONLY if it's not "impossible", error
-        // otherwise.
-        let cube = match colour {
-            "red" if count <= MAX_RED => Cube::Red(count),
-            "green" if count <= MAX_GREEN => Cube::Green(count),
-            "blue" if count <= MAX_BLUE => Cube::Blue(count),
-            _ => return fail(input),
-        };
-
-        Ok((rest, cube))
+    /// `"3 blue, 4 red"` -> `CubeSet { red: 4, green: 0, blue: 3 }`
+    fn parse_reveal(input: &str) -> IResult<&str, CubeSet> {
+        fold_many1(
+            terminated(
+                separated_pair(
+                    u32,
+                    char(' '),
+                    alt((tag("red"), tag("green"), tag("blue"))),
+                ),
+                opt(tag(", ")),
+            ),
+            CubeSet::default,
+            |mut set, (count, colour)| {
+                match colour {
+                    "red" => set.red += count,
+                    "green" => set.green += count,
+                    _ => set.blue += count,
+                }
+                set
+            },
+        )(input)
     }
 }
 
@@ -87,55 +214,95 @@ pub mod part2 {
     use super::*;
 
     pub fn run(input: &str) -> Result<String> {
-        let total = input.lines().fold(0, |acc, line| {
-            let (_, power) = parse_line(line).unwrap();
-
-            acc + power
-        });
+        let total = minimal_sets(input)?
+            .iter()
+            .map(|(_, set)| set.power())
+            .sum::<u64>();
 
         Ok(total.to_string())
     }
 
-    /// Parses the line's/game's "power".
-    pub(super) fn parse_line(line: &str) -> IResult<&str, u32> {
-        preceded(parse_game_id, parse_game_power)(line)
+    /// Returns each game's ID along with the minimal set of cubes that would
+    /// have made the game possible.
+    pub fn minimal_sets(input: &str) -> Result<Vec<(u16, CubeSet)>> {
+        input
+            .lines()
+            .map(|line| {
+                let (_, game) = parse_line(line)
+                    .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+                Ok(game)
+            })
+            .collect()
+    }
+
+    /// Returns the ID and power of the game whose minimal set has the highest
+    /// power. Ties go to the game with the lowest ID.
+    pub fn strongest_game(input: &str) -> Result<(u16, u64)> {
+        games_by_power(input)?
+            .first()
+            .copied()
+            .context("no games in input")
     }
 
-    fn parse_game_id(input: &str) -> IResult<&str, u16> {
-        delimited(tag("Game "), u16, tag(": "))(input)
+    /// Returns every game's ID and the power of its minimal set, sorted by
+    /// power (highest first), ties broken by ID.
+    pub fn games_by_power(input: &str) -> Result<Vec<(u16, u64)>> {
+        let mut games = minimal_sets(input)?
+            .iter()
+            .map(|(id, set)| (*id, set.power()))
+            .collect::<Vec<_>>();
+        games.sort_by_key(|&(id, power)| (Reverse(power), id));
+
+        Ok(games)
     }
 
-    #[derive(Default)]
-    struct CubeSet {
-        red: u32,
-        green: u32,
-        blue: u32,
+    /// Returns each game's ID along with the total number of cubes revealed
+    /// across all of its sets.
+    pub fn total_cubes_per_game(input: &str) -> Result<Vec<(u16, u64)>> {
+        input
+            .lines()
+            .map(|line| {
+                let (_, game) = pair(parse_game_id, parse_total_cubes)(line)
+                    .map_err(|e| anyhow!("failed to parse game: {e}"))?;
+
+                Ok(game)
+            })
+            .collect()
     }
 
-    enum Cube {
-        Red(u32),
-        Green(u32),
-        Blue(u32),
+    /// Parses the line's/game's ID and minimal cube set.
+    pub(super) fn parse_line(line: &str) -> IResult<&str, (u16, CubeSet)> {
+        pair(parse_game_id, parse_minimal_set)(line)
     }
 
-    // Parses input like "3 blue; 1 red, 2 green; 2 green" into 6.
-    fn parse_game_power(input: &str) -> IResult<&str, u32> {
+    // Parses input like "3 blue; 1 red, 2 green; 2 green" into
+    // `CubeSet { red: 1, green: 2, blue: 3 }`.
+    fn parse_minimal_set(input: &str) -> IResult<&str, CubeSet> {
         // `parse_cube` gets rid of separators like ", " and "; ", so we're good
         // to apply it repeatedly with `fold_many1`.
-        let (rest, set) =
-            fold_many1(parse_cube, CubeSet::default, |mut acc, cube| {
-                // Record only the largest numbers of the different cubes
-                match cube {
-                    Cube::Red(c) if c > acc.red => acc.red = c,
-                    Cube::Green(c) if c > acc.green => acc.green = c,
-                    Cube::Blue(c) if c > acc.blue => acc.blue = c,
-                    _ => (),
-                };
-
-                acc
-            })(input)?;
+        fold_many1(parse_cube, CubeSet::default, |mut acc, cube| {
+            // Record only the largest numbers of the different cubes
+            match cube {
+                Cube::Red(c) if c > acc.red => acc.red = c,
+                Cube::Green(c) if c > acc.green => acc.green = c,
+                Cube::Blue(c) if c > acc.blue => acc.blue = c,
+                _ => (),
+            };
+
+            acc
+        })(input)
+    }
 
-        Ok((rest, set.red * set.green * set.blue))
+    // Parses input like "3 blue; 1 red, 2 green; 2 green" into 8.
+    fn parse_total_cubes(input: &str) -> IResult<&str, u64> {
+        fold_many1(
+            parse_cube,
+            || 0,
+            |acc, cube| match cube {
+                Cube::Red(c) | Cube::Green(c) | Cube::Blue(c) => acc + c as u64,
+            },
+        )(input)
     }
 
     /// Parses an input like "2 red" into `Cube::Red(2)`.
@@ -193,6 +360,22 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
         assert_eq!(expected, part1::parse_line(line).unwrap().1);
     }
 
+    #[test]
+    fn part1_repeated_colour() {
+        // Both blues are in the same handful, so that's 20 blue cubes
+        let input = "Game 1: 10 blue, 10 blue
+Game 2: 10 blue; 10 blue";
+        let limits = CubeSet {
+            red: 12,
+            green: 13,
+            blue: 14,
+        };
+
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(2, run_reader(reader, false, &limits).unwrap());
+        assert_eq!("2", part1::run(input).unwrap());
+    }
+
     #[test]
     fn part2_ok() {
         let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
@@ -213,7 +396,178 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
     #[case("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red", 1560)]
     #[case("Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red", 630)]
     #[case("Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green", 36)]
-    fn part2_parse_line_ok(#[case] line: &str, #[case] expected: u32) {
-        assert_eq!(expected, part2::parse_line(line).unwrap().1);
+    fn part2_parse_line_ok(#[case] line: &str, #[case] expected: u64) {
+        let (_, (_, set)) = part2::parse_line(line).unwrap();
+        assert_eq!(expected, set.power());
+    }
+
+    #[test]
+    fn part2_large_power() {
+        let input = "Game 1: 100000 red, 100000 green, 100000 blue";
+
+        assert_eq!("1000000000000000", part2::run(input).unwrap());
+    }
+
+    #[test]
+    fn part2_minimal_sets_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue";
+
+        let sets = part2::minimal_sets(input).unwrap();
+
+        assert_eq!(2, sets.len());
+        assert_eq!(
+            (
+                1,
+                CubeSet {
+                    red: 4,
+                    green: 2,
+                    blue: 6
+                }
+            ),
+            sets[0]
+        );
+    }
+
+    #[test]
+    fn part2_total_cubes_per_game_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue";
+
+        assert_eq!(
+            vec![(1, 18), (2, 13)],
+            part2::total_cubes_per_game(input).unwrap()
+        );
+
+        let input = "Game 1: 4294967295 red; 4294967295 red";
+
+        assert_eq!(
+            vec![(1, 8589934590)],
+            part2::total_cubes_per_game(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn part2_strongest_game_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+        assert_eq!((3, 1560), part2::strongest_game(input).unwrap());
+    }
+
+    #[test]
+    fn part1_first_impossible_reveal_ok() {
+        let limits = CubeSet {
+            red: 12,
+            green: 13,
+            blue: 14,
+        };
+
+        let line = "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red";
+        assert_eq!(Some(0), part1::first_impossible_reveal(line, &limits));
+
+        let line = "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red";
+        assert_eq!(Some(2), part1::first_impossible_reveal(line, &limits));
+
+        let line = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green";
+        assert_eq!(None, part1::first_impossible_reveal(line, &limits));
+    }
+
+    #[test]
+    fn run_reader_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+        let limits = CubeSet {
+            red: 12,
+            green: 13,
+            blue: 14,
+        };
+
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(8, run_reader(reader, false, &limits).unwrap());
+
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(2286, run_reader(reader, true, &limits).unwrap());
+    }
+
+    #[test]
+    fn part2_games_by_power_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+        assert_eq!(
+            vec![(3, 1560), (4, 630), (1, 48), (5, 36), (2, 12)],
+            part2::games_by_power(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn reveal_count_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+        assert_eq!(14, reveal_count(input).unwrap());
+        assert!(reveal_count("Game 1 3 blue").is_err());
+    }
+
+    #[test]
+    fn impossible_game_ids_ok() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+        let limits = CubeSet {
+            red: 12,
+            green: 13,
+            blue: 14,
+        };
+
+        assert_eq!(vec![3, 4], impossible_game_ids(input, &limits).unwrap());
+    }
+
+    #[test]
+    fn validate_sequential_ids_ok() {
+        let input = "Game 1: 3 blue, 4 red
+Game 2: 1 blue, 2 green
+Game 3: 8 green, 6 blue";
+
+        assert!(validate_sequential_ids(input).is_ok());
+
+        let input = "Game 1: 3 blue, 4 red
+Game 2: 1 blue, 2 green
+Game 4: 8 green, 6 blue";
+
+        assert_eq!(
+            "line 3: expected game ID 3, found 4",
+            validate_sequential_ids(input).unwrap_err().to_string()
+        );
+
+        let input = "Game 1: 3 blue, 4 red
+Game 1: 1 blue, 2 green";
+
+        assert_eq!(
+            "line 2: game ID 1 is repeated",
+            validate_sequential_ids(input).unwrap_err().to_string()
+        );
+
+        assert_eq!(
+            "line 1: expected game ID 1, found 0",
+            validate_sequential_ids("Game 0: 3 blue, 4 red")
+                .unwrap_err()
+                .to_string()
+        );
     }
 }
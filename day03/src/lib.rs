@@ -1,21 +1,35 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
-type Position = (i32, i32);
+pub type Position = (i32, i32);
 
-pub mod part1 {
-    use super::*;
+/// A number broken down into its individual digits, along with the position of
+/// each digit.
+type Number = Vec<(Position, char)>;
 
-    pub fn run(input: &str) -> Result<String> {
-        // A list of "digit list"s (a number broken down into individual digits).
-        // With an example input of ".12.....8.", this will store:
-        // [ [((1,0), '1'), ((2,0), '2')], [((8,0), '8')] ]
-        // This repr is useful to cover the positions of the entire number string.
-        let mut numbers: Vec<Vec<(Position, char)>> = Vec::new();
+/// The parsed engine schematic.
+pub struct Schematic {
+    /// A list of "digit list"s (a number broken down into individual digits).
+    /// With an example input of ".12.....8.", this will store:
+    /// [ [((1,0), '1'), ((2,0), '2')], [((8,0), '8')] ]
+    /// This repr is useful to cover the positions of the entire number string.
+    numbers: Vec<Number>,
+
+    /// Mapping of the positions of all symbols encountered to the symbols
+    symbols: HashMap<Position, char>,
 
-        // Set of positions of all symbols encountered
-        let mut symbol_positions: HashSet<Position> = HashSet::new();
+    /// The length of the longest line
+    width: i32,
+
+    /// The number of lines
+    height: i32,
+}
+
+impl Schematic {
+    pub fn parse(input: &str) -> Self {
+        let mut numbers = Vec::new();
+        let mut symbols = HashMap::new();
 
         // Parse the input line-by-line...
         for (y, line) in input.lines().enumerate() {
@@ -40,163 +54,385 @@ pub mod part1 {
                     }
                     '.' => (), // Ignore dots
                     _ => {
-                        symbol_positions.insert((x as i32, y as i32));
+                        symbols.insert((x as i32, y as i32), char);
                     }
                 };
             }
         }
 
-        let total = numbers
+        Self {
+            numbers,
+            symbols,
+            width: input.lines().map(str::len).max().unwrap_or(0) as i32,
+            height: input.lines().count() as i32,
+        }
+    }
+
+    /// Replaces the cell at `pos` with `c` (a digit, a dot or a symbol).
+    ///
+    /// Only the numbers on either side of the cell are rebuilt (they may be
+    /// joined, split up or changed), so edits don't reparse the whole grid.
+    pub fn set_cell(&mut self, pos: Position, c: char) -> Result<()> {
+        let (x, y) = pos;
+        if !(0..self.width).contains(&x) || !(0..self.height).contains(&y) {
+            bail!(
+                "position {:?} is outside the {}x{} schematic",
+                pos,
+                self.width,
+                self.height
+            );
+        }
+        if c.is_whitespace() || c.is_control() {
+            bail!("invalid cell {:?}", c);
+        }
+
+        // Take out every number with a digit in or right next to the cell on
+        // its row, keeping all their other digits around.
+        let mut digits = Vec::new();
+        self.numbers.retain(|number| {
+            let touches = number.iter().any(|&((digit_x, digit_y), _)| {
+                digit_y == y && (digit_x - x).abs() <= 1
+            });
+            if touches {
+                digits.extend(number.iter().filter(|&&(p, _)| p != pos));
+            }
+
+            !touches
+        });
+        self.symbols.remove(&pos);
+
+        match c {
+            '0'..='9' => digits.push((pos, c)),
+            '.' => (),
+            _ => {
+                self.symbols.insert(pos, c);
+            }
+        }
+
+        // Put the digits back together into numbers, where consecutive digits
+        // form a single number, keeping the numbers in reading order.
+        digits.sort_by_key(|&((digit_x, _), _)| digit_x);
+        for number in digits.chunk_by(|&((a, _), _), &((b, _), _)| b == a + 1) {
+            let ((start_x, _), _) = number[0];
+            let at = self.numbers.partition_point(|other| {
+                let ((other_x, other_y), _) = other[0];
+                (other_y, other_x) < (y, start_x)
+            });
+            self.numbers.insert(at, number.to_vec());
+        }
+
+        Ok(())
+    }
+
+    /// Sums up every part number (number adjacent to a symbol).
+    pub fn part_number_sum(&self) -> Result<u64> {
+        let mut total: u64 = 0;
+        for number in &self.numbers {
+            if let Some(part_number) =
+                part1::parse_part_number(&self.symbols, number)?
+            {
+                total = total
+                    .checked_add(part_number)
+                    .context("sum of part numbers overflows u64")?;
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+/// The rule deciding whether two positions are adjacent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Chebyshev distance of 1: 8-way adjacency, diagonals included
+    Chebyshev,
+    /// Manhattan distance of 1: 4-way adjacency, diagonals excluded
+    Manhattan,
+}
+
+/// Returns whether positions `a` and `b` are adjacent under the given metric.
+/// A position is never adjacent to itself.
+pub fn is_adjacent(a: Position, b: Position, metric: Metric) -> bool {
+    let (dx, dy) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+
+    match metric {
+        Metric::Chebyshev => dx.max(dy) == 1,
+        Metric::Manhattan => dx + dy == 1,
+    }
+}
+
+/// Calculates the set of all neighbouring positions of the number.
+fn neighbours(
+    number: &[(Position, char)],
+    metric: Metric,
+) -> HashSet<Position> {
+    let digit_positions =
+        number.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
+
+    number
+        .iter()
+        .flat_map(|&(pos, _)| {
+            // Every position in the 3x3 square around the digit that's
+            // adjacent to it under the metric
+            let (x, y) = pos;
+            (y - 1..=y + 1)
+                .flat_map(move |ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                .filter(move |&neighbour| is_adjacent(pos, neighbour, metric))
+        })
+        // Neighbours that contain digits are not included tho
+        .filter(|pos| !digit_positions.contains(pos))
+        .collect()
+}
+
+/// Combines the list of digits into a full number.
+fn value(number: &[(Position, char)]) -> Result<u64> {
+    let digits = number.iter().map(|(_, digit)| digit).collect::<String>();
+
+    digits
+        .parse::<u64>()
+        .with_context(|| format!("number {} is too large", digits))
+}
+
+/// Counts how many times each distinct symbol appears in the schematic.
+pub fn symbol_frequencies(input: &str) -> HashMap<char, usize> {
+    let schematic = Schematic::parse(input);
+
+    let mut frequencies = HashMap::new();
+    for &symbol in schematic.symbols.values() {
+        *frequencies.entry(symbol).or_insert(0) += 1;
+    }
+
+    frequencies
+}
+
+/// Sums up every number that touches (8-way) another number, for a variant of
+/// the puzzle where numbers count as "parts" when next to other numbers rather
+/// than symbols.
+pub fn number_adjacent_sum(input: &str) -> Result<u64> {
+    let schematic = Schematic::parse(input);
+
+    // Positions of every digit of every number
+    let digit_positions = schematic
+        .numbers
+        .iter()
+        .flat_map(|number| number.iter().map(|&(pos, _)| pos))
+        .collect::<HashSet<_>>();
+
+    let mut total: u64 = 0;
+    for number in &schematic.numbers {
+        // A number's neighbours never include its own digits, so any digit
+        // found there belongs to another number.
+        let touches_number = neighbours(number, Metric::Chebyshev)
             .iter()
-            .filter_map(|number| parse_part_number(&symbol_positions, number))
-            .sum::<u32>();
+            .any(|neighbour| digit_positions.contains(neighbour));
+
+        if touches_number {
+            total = total
+                .checked_add(value(number)?)
+                .context("sum of numbers overflows u64")?;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Groups the part numbers by the row (0-based) they're on, each row's in
+/// left-to-right order. Rows without part numbers are left out.
+pub fn part_numbers_by_row(input: &str) -> Result<BTreeMap<i32, Vec<u32>>> {
+    let schematic = Schematic::parse(input);
+
+    let mut rows: BTreeMap<i32, Vec<u32>> = BTreeMap::new();
+    for number in &schematic.numbers {
+        if let Some(part_number) =
+            part1::parse_part_number(&schematic.symbols, number)?
+        {
+            let ((_, y), _) = number[0];
+            let part_number =
+                u32::try_from(part_number).with_context(|| {
+                    format!("part number {} is too large", part_number)
+                })?;
+
+            rows.entry(y).or_default().push(part_number);
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Returns the top-left and bottom-right corners of the smallest rectangle
+/// covering every digit of every part number, None if there are no part
+/// numbers.
+pub fn part_number_bounds(input: &str) -> Result<Option<(Position, Position)>> {
+    let schematic = Schematic::parse(input);
+
+    let mut bounds: Option<(Position, Position)> = None;
+    for number in &schematic.numbers {
+        if part1::parse_part_number(&schematic.symbols, number)?.is_none() {
+            continue;
+        }
+
+        for &((x, y), _) in number {
+            let ((min_x, min_y), (max_x, max_y)) =
+                bounds.get_or_insert(((x, y), (x, y)));
+            *min_x = (*min_x).min(x);
+            *min_y = (*min_y).min(y);
+            *max_x = (*max_x).max(x);
+            *max_y = (*max_y).max(y);
+        }
+    }
+
+    Ok(bounds)
+}
+
+pub mod part1 {
+    use super::*;
+
+    pub fn run(input: &str) -> Result<String> {
+        let total = Schematic::parse(input).part_number_sum()?;
 
         Ok(total.to_string())
     }
 
     /// Returns some part number if the given number is adjacent to any symbol,
     /// None otherwise.
-    fn parse_part_number(
-        symbols: &HashSet<Position>,
+    pub(super) fn parse_part_number(
+        symbols: &HashMap<Position, char>,
         number: &[(Position, char)],
-    ) -> Option<u32> {
-        let digit_positions =
-            number.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
-
-        // Calculate set of all neighbouring positions of the number
-        let neighbours = number
-            .iter()
-            .flat_map(|&((x, y), _)| {
-                [
-                    (x, y - 1),     // top
-                    (x, y + 1),     // bottom
-                    (x - 1, y),     // left
-                    (x + 1, y),     // right
-                    (x - 1, y - 1), // top-left
-                    (x + 1, y - 1), // top-right
-                    (x - 1, y + 1), // bottom-left
-                    (x + 1, y + 1), // bottom-right
-                ]
-            })
-            // Neighbours that contain digits are not included tho
-            .filter(|pos| !digit_positions.contains(pos))
-            .collect::<HashSet<_>>();
-
+    ) -> Result<Option<u64>> {
         // If any of the number's neighbours is a symbol, we consider it a
         // part number.
-        neighbours
+        neighbours(number, Metric::Chebyshev)
             .iter()
-            .any(|neighbour| symbols.get(neighbour).is_some())
-            .then(|| {
-                // Combine the list of digits into a full number
-                number
-                    .iter()
-                    .map(|(_, digit)| digit)
-                    .collect::<String>()
-                    .parse::<u32>()
-                    .unwrap()
-            })
+            .any(|neighbour| symbols.contains_key(neighbour))
+            .then(|| value(number))
+            .transpose()
     }
 }
 
 pub mod part2 {
     use super::*;
 
-    pub fn run(input: &str) -> Result<String> {
-        // Repr of a list of "digit list"s (numbers), similar to part 1 above.
-        let mut numbers: Vec<Vec<(Position, char)>> = Vec::new();
-
-        // Set of positions of only asterisk symbols
-        let mut asterisks: HashSet<Position> = HashSet::new();
-
-        // Mapping of possible gears (asterisks with >=2 adjacent part numbers)
-        let mut gear_candidates: HashMap<Position, (usize, u32)> =
-            HashMap::new();
+    /// A gear: an asterisk with >=2 adjacent part numbers.
+    #[derive(Debug, PartialEq)]
+    pub struct GearDetail {
+        pub pos: Position,
+        /// The adjacent part numbers, in reading order
+        pub factors: Vec<u32>,
+        /// The product of all the factors
+        pub ratio: u64,
+    }
 
-        // Parse the input line-by-line...
-        for (y, line) in input.lines().enumerate() {
-            let mut char_iter = line.char_indices().peekable();
+    pub fn run(input: &str) -> Result<String> {
+        // We sum up the gear ratios of all gears for the puzzle answer.
+        let total = gears(input)?.into_iter().try_fold(
+            0u64,
+            |acc, (pos, factors)| {
+                acc.checked_add(gear_ratio(pos, factors)?)
+                    .context("sum of gear ratios overflows u64")
+            },
+        )?;
 
-            // ...and char-by-char in each line.
-            while let Some((x, char)) = char_iter.next() {
-                match char {
-                    '*' => {
-                        asterisks.insert((x as i32, y as i32));
-                    }
-                    '0'..='9' => {
-                        let mut number = Vec::new();
-                        number.push(((x as i32, y as i32), char));
+        Ok(total.to_string())
+    }
 
-                        // Consume consecutive digits while there's any
-                        while let Some((_, '0'..='9')) = char_iter.peek() {
-                            let (next_x, next_digit) =
-                                char_iter.next().unwrap();
-                            number
-                                .push(((next_x as i32, y as i32), next_digit));
-                        }
+    /// Lists every gear in reading order, along with its adjacent part numbers
+    /// and gear ratio.
+    ///
+    /// Errors if a part number adjacent to a gear doesn't fit in a `u32`.
+    pub fn gear_details(input: &str) -> Result<Vec<GearDetail>> {
+        gears(input)?
+            .into_iter()
+            .map(|(pos, factors)| {
+                let factors = factors
+                    .into_iter()
+                    .map(|factor| {
+                        u32::try_from(factor).with_context(|| {
+                            format!("gear factor {} is too large", factor)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let ratio = gear_ratio(
+                    pos,
+                    factors.iter().map(|&factor| factor as u64),
+                )?;
+
+                Ok(GearDetail {
+                    pos,
+                    factors,
+                    ratio,
+                })
+            })
+            .collect()
+    }
 
-                        numbers.push(number);
-                    }
-                    _ => (), // Only care about '*' and numbers, ignore others
-                };
-            }
-        }
+    /// Lists every gear's position in reading order, along with its adjacent
+    /// part numbers.
+    fn gears(input: &str) -> Result<Vec<(Position, Vec<u64>)>> {
+        let schematic = Schematic::parse(input);
 
-        for number in numbers {
-            let digit_positions =
-                number.iter().map(|&(pos, _)| pos).collect::<Vec<_>>();
-
-            // Calculate set of all neighbouring positions of the number
-            let neighbours = number
-                .iter()
-                .flat_map(|&((x, y), _)| {
-                    [
-                        (x, y - 1),     // top
-                        (x, y + 1),     // bottom
-                        (x - 1, y),     // left
-                        (x + 1, y),     // right
-                        (x - 1, y - 1), // top-left
-                        (x + 1, y - 1), // top-right
-                        (x - 1, y + 1), // bottom-left
-                        (x + 1, y + 1), // bottom-right
-                    ]
-                })
-                // Neighbours that contain digits are not included tho
-                .filter(|pos| !digit_positions.contains(pos))
-                .collect::<HashSet<_>>();
+        // Mapping of possible gears (asterisks) to their adjacent numbers
+        let mut gear_candidates: HashMap<Position, Vec<u64>> = HashMap::new();
 
+        for number in &schematic.numbers {
             // For each neighbour of the number, if it happens to be an asterisk,
-            // we add it as a gear candidate, keeping track of the number of
-            // numbers it has seen, along with the actual numbers.
-            for neighbour in neighbours {
-                if asterisks.get(&neighbour).is_some() {
-                    // Combine the list of digits into a full number
-                    let number = number
-                        .iter()
-                        .map(|(_, digit)| digit)
-                        .collect::<String>()
-                        .parse::<u32>()
-                        .unwrap();
-
+            // we add it as a gear candidate, keeping track of the numbers it
+            // has seen.
+            for neighbour in neighbours(number, Metric::Chebyshev) {
+                if schematic.symbols.get(&neighbour) == Some(&'*') {
                     gear_candidates
                         .entry(neighbour)
-                        .and_modify(|(count, num)| {
-                            *count += 1;
-                            *num *= number;
-                        })
-                        .or_insert((1, number));
+                        .or_default()
+                        .push(value(number)?);
                 }
             }
         }
 
-        // Any asterisks with >=2 numbers are gears, and we sum up their gear
-        // ratios for the puzzle answer.
-        let total = gear_candidates
+        // Any asterisks with >=2 numbers are gears
+        let mut gears = gear_candidates
+            .into_iter()
+            .filter(|(_, factors)| factors.len() >= 2)
+            .collect::<Vec<_>>();
+        gears.sort_by_key(|(pos, _)| (pos.1, pos.0));
+
+        Ok(gears)
+    }
+
+    /// Multiplies the factors of the gear at `pos` together.
+    fn gear_ratio(
+        pos: Position,
+        factors: impl IntoIterator<Item = u64>,
+    ) -> Result<u64> {
+        factors
+            .into_iter()
+            .try_fold(1u64, |acc, factor| acc.checked_mul(factor))
+            .with_context(|| format!("gear ratio at {:?} overflows u64", pos))
+    }
+
+    /// Returns the gear ratio of the asterisk at `pos` if it is a gear (has >=2
+    /// adjacent part numbers), None otherwise.
+    ///
+    /// Errors if there's no asterisk at `pos`.
+    pub fn gear_ratio_at(input: &str, pos: Position) -> Result<Option<u64>> {
+        let schematic = Schematic::parse(input);
+
+        if schematic.symbols.get(&pos) != Some(&'*') {
+            bail!("no asterisk at position {:?}", pos);
+        }
+
+        let adjacent = schematic
+            .numbers
             .iter()
-            .filter(|(_, (count, _))| *count >= 2)
-            .fold(0, |acc, (_, (_, number))| acc + number);
+            .filter(|number| {
+                neighbours(number, Metric::Chebyshev).contains(&pos)
+            })
+            .map(|number| value(number))
+            .collect::<Result<Vec<_>>>()?;
 
-        Ok(total.to_string())
+        if adjacent.len() < 2 {
+            return Ok(None);
+        }
+
+        gear_ratio(pos, adjacent).map(Some)
     }
 }
 
@@ -235,4 +471,200 @@ mod tests {
 
         assert_eq!("467835", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn part2_gear_ratio_at_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        assert_eq!(Some(16345), part2::gear_ratio_at(input, (3, 1)).unwrap());
+        assert_eq!(Some(451490), part2::gear_ratio_at(input, (5, 8)).unwrap());
+        assert_eq!(None, part2::gear_ratio_at(input, (3, 4)).unwrap());
+        assert!(part2::gear_ratio_at(input, (0, 0)).is_err());
+    }
+
+    #[test]
+    fn is_adjacent_ok() {
+        assert!(is_adjacent((1, 1), (2, 1), Metric::Chebyshev));
+        assert!(is_adjacent((1, 1), (2, 2), Metric::Chebyshev));
+        assert!(!is_adjacent((1, 1), (3, 1), Metric::Chebyshev));
+        assert!(!is_adjacent((1, 1), (1, 1), Metric::Chebyshev));
+
+        assert!(is_adjacent((1, 1), (1, 0), Metric::Manhattan));
+        assert!(!is_adjacent((1, 1), (2, 2), Metric::Manhattan));
+        assert!(!is_adjacent((1, 1), (1, 1), Metric::Manhattan));
+    }
+
+    #[test]
+    fn large_numbers_ok() {
+        let input = "4294967296.
+*..........
+18446744073709551615";
+
+        assert_eq!("8589934592", part1::run("4294967296*4294967296").unwrap());
+        assert!(part1::run(input).is_err());
+
+        let input = "4294967296*3";
+        assert_eq!("12884901888", part2::run(input).unwrap());
+
+        let input = "9999999999*9999999999";
+        assert!(part2::run(input).is_err());
+        assert!(part2::gear_ratio_at(input, (10, 0)).is_err());
+    }
+
+    #[test]
+    fn number_adjacent_sum_ok() {
+        let input = "12.....
+..34...
+......5
+7*.....";
+
+        assert_eq!(46, number_adjacent_sum(input).unwrap());
+    }
+
+    #[test]
+    fn symbol_frequencies_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        let frequencies = symbol_frequencies(input);
+
+        assert_eq!(Some(&3), frequencies.get(&'*'));
+        assert_eq!(
+            HashMap::from([('*', 3), ('#', 1), ('+', 1), ('$', 1)]),
+            frequencies
+        );
+    }
+
+    #[test]
+    fn part2_gear_details_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        assert_eq!(
+            vec![
+                part2::GearDetail {
+                    pos: (3, 1),
+                    factors: vec![467, 35],
+                    ratio: 16345,
+                },
+                part2::GearDetail {
+                    pos: (5, 8),
+                    factors: vec![755, 598],
+                    ratio: 451490,
+                },
+            ],
+            part2::gear_details(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn schematic_set_cell_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        let mut schematic = Schematic::parse(input);
+        assert_eq!(4361, schematic.part_number_sum().unwrap());
+
+        // A symbol next to 114 makes it a part number
+        schematic.set_cell((8, 0), '#').unwrap();
+        assert_eq!(4475, schematic.part_number_sum().unwrap());
+
+        // Splitting 467 leaves 46, which touches no symbol
+        schematic.set_cell((2, 0), '.').unwrap();
+        assert_eq!(4008, schematic.part_number_sum().unwrap());
+
+        // Joining 35 and 633 into 3576633
+        schematic.set_cell((4, 2), '7').unwrap();
+        schematic.set_cell((5, 2), '6').unwrap();
+        assert_eq!(
+            4008 - 35 - 633 + 3576633,
+            schematic.part_number_sum().unwrap()
+        );
+
+        assert!(schematic.set_cell((10, 0), '#').is_err());
+        assert!(schematic.set_cell((0, 0), ' ').is_err());
+    }
+
+    #[test]
+    fn part_numbers_by_row_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        let rows = part_numbers_by_row(input).unwrap();
+
+        assert_eq!(Some(&vec![467]), rows.get(&0));
+        assert_eq!(Some(&vec![35, 633]), rows.get(&2));
+        assert_eq!(None, rows.get(&1));
+        assert_eq!(None, rows.get(&5));
+    }
+
+    #[test]
+    fn part_number_bounds_ok() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+        assert_eq!(Some(((0, 0), (8, 9))), part_number_bounds(input).unwrap());
+        assert_eq!(
+            Some(((1, 1), (2, 1))),
+            part_number_bounds("....\n.12*\n.7..").unwrap()
+        );
+        assert_eq!(None, part_number_bounds("114.\n....").unwrap());
+    }
+
+    #[test]
+    fn part2_gear_details_large_factor_err() {
+        let input = "4294967296*3";
+
+        assert_eq!("12884901888", part2::run(input).unwrap());
+        assert!(part2::gear_details(input).is_err());
+    }
 }
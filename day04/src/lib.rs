@@ -1,11 +1,19 @@
 use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use nom::bytes::complete::{tag, take_until1};
 use nom::character::complete::{space0, space1, u32};
 use nom::multi::separated_list1;
-use nom::sequence::{delimited, preceded, separated_pair};
+use nom::sequence::{delimited, pair, preceded, separated_pair};
 use nom::IResult;
+use num::{BigUint, CheckedAdd, One};
+
+/// Sums up the points of every card as scored in part 1, where cards win
+/// points rather than copies, for comparing against the part 2 card count.
+pub fn flat_points(input: &str) -> Result<u64> {
+    part1::run_reader(input.as_bytes())
+}
 
 pub mod part1 {
     use super::*;
@@ -20,6 +28,18 @@ pub mod part1 {
         Ok(total.to_string())
     }
 
+    /// Sums up the points of the cards read line-by-line from `reader`, so the
+    /// whole deck never has to be in memory at once.
+    pub fn run_reader<R: BufRead>(reader: R) -> Result<u64> {
+        reader.lines().try_fold(0, |acc, line| {
+            let line = line?;
+            let (_, points) = parse_line(&line)
+                .map_err(|e| anyhow!("failed to parse card: {e}"))?;
+
+            Ok(acc + points as u64)
+        })
+    }
+
     /// Parses the number of points the input card is worth.
     pub(super) fn parse_line(input: &str) -> IResult<&str, u32> {
         // Split "Card x: " from rest of string
@@ -51,55 +71,151 @@ pub mod part2 {
     use super::*;
 
     pub fn run(input: &str) -> Result<String> {
-        // List of matching numbers
-        let matches = input
-            .lines()
-            .map(|line| parse_line(line).unwrap().1)
-            .collect::<Vec<_>>();
+        let total = card_counts(input)?
+            .iter()
+            .try_fold(0_u64, |total, (_, count)| total.checked_add(*count))
+            .context("total overflows u64")?;
+
+        Ok(total.to_string())
+    }
 
-        // Counter of card copies
-        let mut copies: HashMap<u32, u32> = HashMap::new();
+    /// Returns the card IDs sorted by how many instances of them we end up
+    /// with (most first), ties broken by ID.
+    pub fn cards_by_copies(input: &str) -> Result<Vec<(u32, u64)>> {
+        let mut counts = card_counts(input)?;
+        counts.sort_by(|(a_id, a_count), (b_id, b_count)| {
+            b_count.cmp(a_count).then(a_id.cmp(b_id))
+        });
 
-        for (i, match_count) in matches.iter().enumerate() {
-            let i = i as u32 + 1;
+        Ok(counts)
+    }
 
-            // For the original card, keep track of copies won
-            for id in (i + 1)..(i + 1 + match_count) {
-                copies
-                    .entry(id)
-                    .and_modify(|instance| *instance += 1)
-                    .or_insert(1);
-            }
+    /// Like `run`, but only adding up the instances of the cards with the
+    /// given IDs. Copies are still won across the whole deck.
+    ///
+    /// Errors if any of the IDs isn't in the deck.
+    pub fn total_for_ids(input: &str, ids: &[u32]) -> Result<u64> {
+        let counts: HashMap<u32, u64> =
+            card_counts(input)?.into_iter().collect();
+
+        ids.iter().try_fold(0_u64, |total, id| {
+            let count = counts
+                .get(id)
+                .with_context(|| format!("no card with ID {}", id))?;
+
+            total.checked_add(*count).context("total overflows u64")
+        })
+    }
+
+    /// Returns the ID of the card we end up with the most instances of, along
+    /// with that count. Ties go to the lowest ID.
+    pub fn max_copies(input: &str) -> Result<(u32, u64)> {
+        cards_by_copies(input)?
+            .first()
+            .copied()
+            .context("no cards in input")
+    }
+
+    /// Returns the IDs of the cards whose matches win copies of `card_id`,
+    /// i.e. every earlier card whose run of won cards reaches it.
+    pub fn copies_source(input: &str, card_id: u32) -> Result<Vec<u32>> {
+        let matches = match_counts(input)?;
+
+        if !matches.iter().any(|&(id, _)| id == card_id) {
+            bail!("no card with ID {}", card_id);
+        }
+
+        // Card `id` wins copies of cards `id + 1..=id + match_count`
+        Ok(matches
+            .iter()
+            .filter(|&&(id, match_count)| {
+                id < card_id && id + match_count >= card_id
+            })
+            .map(|&(id, _)| id)
+            .collect())
+    }
+
+    /// Like `run`, but counting card instances with big integers, for decks
+    /// where the copies won grow well past what `u64` can hold.
+    pub fn run_big(input: &str) -> Result<String> {
+        let matches = match_counts(input)?;
+
+        let total = card_instances::<BigUint>(&matches)
+            .expect("BigUint addition never overflows")
+            .into_iter()
+            .map(|(_, count)| count)
+            .sum::<BigUint>();
+
+        Ok(total.to_string())
+    }
 
-            // For the _copy_ of the card, keep track of further copies won
-            if let Some(&count) = copies.get(&i) {
-                for id in (i + 1)..(i + 1 + match_count) {
-                    copies
-                        .entry(id)
-                        // `*instance += count` instead of `+= 1` here because
-                        // _each_ copy wins more copies.
-                        .and_modify(|instance| *instance += count)
-                        .or_insert(1);
+    /// Returns each card's ID along with how many instances of it we end up
+    /// with (the original plus all copies won).
+    ///
+    /// Errors if any count overflows `u64`.
+    fn card_counts(input: &str) -> Result<Vec<(u32, u64)>> {
+        let matches = match_counts(input)?;
+
+        card_instances::<u64>(&matches).context("card count overflows u64")
+    }
+
+    /// Works out how many instances of each card we end up with, given each
+    /// card's ID and match count. Returns None on overflow.
+    fn card_instances<N>(matches: &[(u32, u32)]) -> Option<Vec<(u32, N)>>
+    where
+        N: Clone + CheckedAdd + One,
+    {
+        // Counter of card instances, starting with just the originals. Cards
+        // are keyed by their own IDs, so it doesn't matter what number the
+        // first card starts at.
+        let mut instances: HashMap<u32, N> =
+            matches.iter().map(|&(id, _)| (id, N::one())).collect();
+
+        for &(i, match_count) in matches {
+            // By the time we get to a card, all copies of it have been won, and
+            // _each_ instance of it wins one more copy of the next cards.
+            let count = instances[&i].clone();
+
+            for id in (i + 1)..(i + 1 + match_count) {
+                // Cards past the end of the deck can't be won
+                if let Some(instance) = instances.get_mut(&id) {
+                    *instance = instance.checked_add(&count)?;
                 }
             }
         }
 
-        // Total = number of copies + number of originals
-        let total = copies.values().sum::<u32>() + matches.len() as u32;
+        Some(
+            matches
+                .iter()
+                .map(|&(id, _)| (id, instances[&id].clone()))
+                .collect(),
+        )
+    }
+
+    /// Parses the ID and count of matching numbers of every card in order.
+    fn match_counts(input: &str) -> Result<Vec<(u32, u32)>> {
+        input
+            .lines()
+            .map(|line| {
+                let (_, card) = parse_line(line)
+                    .map_err(|e| anyhow!("failed to parse card: {e}"))?;
 
-        Ok(total.to_string())
+                Ok(card)
+            })
+            .collect()
     }
 
-    /// Parses the count of matching numbers the input card has.
-    fn parse_line(input: &str) -> IResult<&str, u32> {
-        // Split "Card x: " from rest of string
-        let (rest, _) = preceded(take_until1(": "), tag(": "))(input)?;
+    /// Parses the ID and the count of matching numbers the input card has.
+    fn parse_line(input: &str) -> IResult<&str, (u32, u32)> {
+        // Split "Card x:" from rest of string, keeping the ID
+        let (rest, id) =
+            delimited(pair(tag("Card"), space1), u32, tag(":"))(input)?;
 
         // Split sets of winning numbers and our numbers
         let (rest, (winning, ours)) =
             separated_pair(parse_numbers, tag("|"), parse_numbers)(rest)?;
 
-        Ok((rest, winning.intersection(&ours).count() as u32))
+        Ok((rest, (id, winning.intersection(&ours).count() as u32)))
     }
 
     /// Parses the space-delimited set of numbers.
@@ -129,6 +245,19 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
         assert_eq!("13", part1::run(input).unwrap());
     }
 
+    #[test]
+    fn part1_run_reader_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(13, part1::run_reader(reader).unwrap());
+    }
+
     #[rstest]
     #[case("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53", 8)]
     #[case("Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19", 2)]
@@ -151,4 +280,113 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
         assert_eq!("30", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn part2_cards_by_copies_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!(
+            vec![(5, 14), (4, 8), (3, 4), (2, 2), (1, 1), (6, 1)],
+            part2::cards_by_copies(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn part2_copies_source_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!(vec![1, 2, 3], part2::copies_source(input, 4).unwrap());
+        assert_eq!(vec![1, 3, 4], part2::copies_source(input, 5).unwrap());
+        assert!(part2::copies_source(input, 1).unwrap().is_empty());
+        assert!(part2::copies_source(input, 7).is_err());
+    }
+
+    #[test]
+    fn part2_zero_based_ids_ok() {
+        let input = "Card 0: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 1: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 2:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 3: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 4: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 5: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!("30", part2::run(input).unwrap());
+        assert_eq!(
+            vec![(4, 14), (3, 8), (2, 4), (1, 2), (0, 1), (5, 1)],
+            part2::cards_by_copies(input).unwrap()
+        );
+        assert_eq!(vec![0, 1, 2], part2::copies_source(input, 3).unwrap());
+    }
+
+    #[test]
+    fn part2_run_big_ok() {
+        // Every card wins a copy of every card after it, so card `k` ends up
+        // with 2^(k - 1) instances: 2^65 - 1 in total.
+        let input = (1..=65)
+            .map(|id| match 65 - id {
+                0 => format!("Card {id}: 1 | 2"),
+                matches => {
+                    let numbers = (1..=matches)
+                        .map(|n| n.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    format!("Card {id}: {numbers} | {numbers}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(part2::run(&input).is_err());
+        assert_eq!("36893488147419103231", part2::run_big(&input).unwrap());
+    }
+
+    #[test]
+    fn part2_total_for_ids_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!(7, part2::total_for_ids(input, &[1, 2, 3]).unwrap());
+        assert_eq!(0, part2::total_for_ids(input, &[]).unwrap());
+        assert!(part2::total_for_ids(input, &[7]).is_err());
+    }
+
+    #[test]
+    fn part2_max_copies_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!((5, 14), part2::max_copies(input).unwrap());
+        assert!(part2::max_copies("").is_err());
+    }
+
+    #[test]
+    fn flat_points_ok() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+        assert_eq!(13, flat_points(input).unwrap());
+        assert_eq!("30", part2::run(input).unwrap());
+    }
 }
@@ -1,11 +1,17 @@
-use anyhow::Result;
-use nom::bytes::complete::{tag, take_till1, take_until1};
-use nom::character::complete::{line_ending, multispace1, space1, u64};
-use nom::combinator::map;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use nom::bytes::complete::{tag, take_until1};
+use nom::character::complete::{line_ending, space1, u64};
+use nom::combinator::map_res;
 use nom::multi::separated_list1;
-use nom::sequence::{preceded, separated_pair, terminated};
+use nom::sequence::{preceded, terminated, tuple};
 use nom::IResult;
 
+/// A half-open range of numbers, e.g. seeds or locations.
+pub type Range = std::ops::Range<u64>;
+
 struct RangeMap {
     src_start: u64,
     dest_start: u64,
@@ -13,6 +19,35 @@ struct RangeMap {
 }
 
 impl RangeMap {
+    /// Makes a range map, rejecting empty ranges and ranges whose source or
+    /// destination end goes past `u64::MAX`. Every range map is made this way,
+    /// so the other methods can add `range_len` to either start freely.
+    fn try_new(
+        src_start: u64,
+        dest_start: u64,
+        range_len: u64,
+    ) -> Result<Self> {
+        if range_len == 0 {
+            bail!("range map from {} to {} is empty", src_start, dest_start);
+        }
+        if src_start.checked_add(range_len).is_none()
+            || dest_start.checked_add(range_len).is_none()
+        {
+            bail!(
+                "range map from {} to {} of length {} overflows u64",
+                src_start,
+                dest_start,
+                range_len
+            );
+        }
+
+        Ok(Self {
+            src_start,
+            dest_start,
+            range_len,
+        })
+    }
+
     /// Returns the destination version of `num` if in range, None otherwise.
     fn map(&self, num: u64) -> Option<u64> {
         #[allow(clippy::unnecessary_lazy_evaluations)]
@@ -23,6 +58,725 @@ impl RangeMap {
             // when `num` isn't in the range, potentially leading to overflow!
             .then(|| self.dest_start + (num - self.src_start))
     }
+
+    /// Maps the part of `range` that overlaps this range map, returning the
+    /// mapped part (if any) along with the leftover parts that weren't mapped.
+    fn map_range(&self, range: &Range) -> (Option<Range>, Vec<Range>) {
+        let start = range.start.max(self.src_start);
+        let end = range.end.min(self.src_start + self.range_len);
+
+        // No overlap, nothing gets mapped
+        if start >= end {
+            return (None, vec![range.clone()]);
+        }
+
+        let mapped_start = self.dest_start + (start - self.src_start);
+        let mapped = mapped_start..(mapped_start + (end - start));
+
+        // Whatever sticks out on either side of the overlap is left over
+        let leftovers = [range.start..start, end..range.end]
+            .into_iter()
+            .filter(|leftover| !leftover.is_empty())
+            .collect();
+
+        (Some(mapped), leftovers)
+    }
+
+    /// Returns the part of `range` (a destination range) that this range map
+    /// maps to, as the source range it comes from. None if they don't overlap.
+    fn unmap_range(&self, range: &Range) -> Option<Range> {
+        let start = range.start.max(self.dest_start);
+        let end = range.end.min(self.dest_start + self.range_len);
+
+        (start < end).then(|| {
+            (self.src_start + (start - self.dest_start))
+                ..(self.src_start + (end - self.dest_start))
+        })
+    }
+}
+
+/// A single map section of the almanac, e.g. `seed-to-soil`.
+struct Section {
+    name: String,
+    ranges: Vec<RangeMap>,
+}
+
+impl Section {
+    /// Maps `num` through the first range map that covers it. Numbers not
+    /// covered by any range map are mapped to themselves.
+    fn map(&self, num: u64) -> u64 {
+        self.ranges
+            .iter()
+            .find_map(|range_map| range_map.map(num))
+            .unwrap_or(num)
+    }
+
+    /// Maps every range through the section, splitting ranges that are only
+    /// partially covered by a range map. Parts not covered by any range map
+    /// are mapped to themselves.
+    fn map_ranges(&self, ranges: Vec<Range>) -> Vec<Range> {
+        let mut mapped = Vec::new();
+        let mut unmapped = ranges;
+
+        for range_map in &self.ranges {
+            let mut leftovers = Vec::new();
+
+            for range in &unmapped {
+                let (mapped_part, rest) = range_map.map_range(range);
+                mapped.extend(mapped_part);
+                leftovers.extend(rest);
+            }
+
+            unmapped = leftovers;
+        }
+
+        mapped.extend(unmapped);
+        mapped
+    }
+
+    /// The reverse of `map_ranges`: returns every source range whose numbers
+    /// get mapped into one of `ranges`.
+    fn unmap_ranges(&self, ranges: &[Range]) -> Vec<Range> {
+        let pieces = ranges
+            .iter()
+            .map(|range| (range.clone(), 0))
+            .collect::<Vec<_>>();
+
+        self.unmap_shifted(&pieces)
+            .into_iter()
+            .map(|(source, _)| source)
+            .collect()
+    }
+
+    /// Like `unmap_ranges`, but for ranges paired with how far they're shifted
+    /// further down the pipeline. Each source range comes paired with how far
+    /// it gets shifted from here on, this section included.
+    fn unmap_shifted(&self, pieces: &[(Range, i128)]) -> Vec<(Range, i128)> {
+        let mut sources = Vec::new();
+
+        // Source ranges of the range maps seen so far. A number covered by one
+        // of them never makes it to a later range map.
+        let mut claimed: Vec<Range> = Vec::new();
+
+        for range_map in &self.ranges {
+            let shift =
+                range_map.dest_start as i128 - range_map.src_start as i128;
+
+            for (range, offset) in pieces {
+                let Some(source) = range_map.unmap_range(range) else {
+                    continue;
+                };
+
+                let mut parts = vec![source];
+                for cut in &claimed {
+                    parts = subtract(parts, cut);
+                }
+                sources.extend(
+                    parts.into_iter().map(|part| (part, offset + shift)),
+                );
+            }
+
+            claimed.push(
+                range_map.src_start
+                    ..(range_map.src_start + range_map.range_len),
+            );
+        }
+
+        // Numbers not covered by any range map are mapped to themselves
+        for (range, offset) in pieces {
+            let mut parts = vec![range.clone()];
+            for cut in &claimed {
+                parts = subtract(parts, cut);
+            }
+            sources.extend(parts.into_iter().map(|part| (part, *offset)));
+        }
+
+        sources
+    }
+}
+
+/// Removes the numbers in `cut` from every range, splitting ranges that `cut`
+/// falls in the middle of.
+fn subtract(ranges: Vec<Range>, cut: &Range) -> Vec<Range> {
+    ranges
+        .into_iter()
+        .flat_map(|range| {
+            [
+                range.start..range.end.min(cut.start),
+                range.start.max(cut.end)..range.end,
+            ]
+        })
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Sorts the ranges, merging the ones that overlap or touch.
+fn merge(ranges: Vec<Range>) -> Vec<Range> {
+    merge_if(ranges, |last, range| range.start <= last.end)
+}
+
+/// Sorts the ranges, merging only the ones that overlap, so ranges that merely
+/// touch are kept apart.
+fn merge_overlapping(ranges: Vec<Range>) -> Vec<Range> {
+    merge_if(ranges, |last, range| range.start < last.end)
+}
+
+/// Sorts the ranges, merging each one into the one before it if `joins` says
+/// so.
+fn merge_if(
+    mut ranges: Vec<Range>,
+    joins: impl Fn(&Range, &Range) -> bool,
+) -> Vec<Range> {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if joins(last, &range) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// The map sections of an almanac, in pipeline order.
+///
+/// Numbers go through it forwards: from the first section's source category
+/// (normally `seed`), through every section in turn, down to the last
+/// section's destination category (normally `location`).
+pub struct Pipeline {
+    sections: Vec<Section>,
+}
+
+impl FromStr for Pipeline {
+    type Err = anyhow::Error;
+
+    /// Parses the map sections of an almanac (everything after the seeds
+    /// line), putting them in pipeline order.
+    ///
+    /// Errors if anything but whitespace follows the last map section.
+    fn from_str(s: &str) -> Result<Self> {
+        let (rest, sections) =
+            separated_list1(tag("\n\n"), parse_section)(s.trim_start())
+                .map_err(|e| anyhow!("failed to parse map sections: {e}"))?;
+
+        if let Some(line) = rest.lines().find(|line| !line.trim().is_empty()) {
+            bail!("unexpected input after the map sections: {:?}", line);
+        }
+
+        Ok(Self {
+            sections: route(sections)?,
+        })
+    }
+}
+
+impl Pipeline {
+    /// Passes `n` forwards through every map section in order, e.g. a seed
+    /// number to its location number.
+    pub fn map(&self, n: u64) -> u64 {
+        self.sections
+            .iter()
+            .fold(n, |num, section| section.map(num))
+    }
+
+    /// Passes a range forwards through every map section in order, returning
+    /// the pieces it ends up split into (unsorted).
+    pub fn map_range(&self, r: Range) -> Vec<Range> {
+        self.sections
+            .iter()
+            .fold(vec![r], |ranges, section| section.map_ranges(ranges))
+    }
+}
+
+/// The parsed almanac: the list of seeds plus all map sections in order.
+pub struct Almanac {
+    seeds: Vec<u64>,
+    pipeline: Pipeline,
+}
+
+impl Almanac {
+    /// Parses the full puzzle input into an almanac.
+    pub fn parse(input: &str) -> Result<Self> {
+        check_seeds_line(input)?;
+
+        let (rest, seeds) = parse_seeds(input)
+            .map_err(|e| anyhow!("failed to parse almanac: {e}"))?;
+
+        Ok(Self {
+            seeds,
+            pipeline: rest.parse()?,
+        })
+    }
+
+    /// The seed numbers listed on the first line of the almanac.
+    pub fn seeds(&self) -> &[u64] {
+        &self.seeds
+    }
+
+    /// The map sections the seeds go through.
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// Passes `seed` through every map section in order, returning the final
+    /// location number.
+    pub fn seed_to_location(&self, seed: u64) -> u64 {
+        self.pipeline.map(seed)
+    }
+
+    /// Maps `value` from the given category (e.g. `"soil"`) all the way down
+    /// to a location number, following the sections by their names
+    /// (`soil-to-fertilizer`, then `fertilizer-to-water`, and so on).
+    pub fn map_from(&self, category: &str, value: u64) -> Result<u64> {
+        let mut category = category;
+        let mut value = value;
+
+        // Each section can only be passed through once on the way down
+        for _ in 0..=self.pipeline.sections.len() {
+            if category == "location" {
+                return Ok(value);
+            }
+
+            let (section, destination) = self
+                .pipeline
+                .sections
+                .iter()
+                .find_map(|section| {
+                    let (source, destination) =
+                        section.name.split_once("-to-")?;
+                    (source == category).then_some((section, destination))
+                })
+                .with_context(|| {
+                    format!("no map section from category {:?}", category)
+                })?;
+
+            value = section.map(value);
+            category = destination;
+        }
+
+        bail!("map sections from {:?} never reach location", category)
+    }
+
+    /// The seed numbers read as pairs of range start and range length.
+    ///
+    /// `[79, 14, 55, 13]` -> `[79..93, 55..68]`
+    ///
+    /// Ranges of length 0 hold no seeds, so they're left out.
+    ///
+    /// Errors if there's an odd number of seed numbers, or if a range goes past
+    /// `u64::MAX`.
+    pub fn seed_ranges(&self) -> Result<Vec<Range>> {
+        if !self.seeds.len().is_multiple_of(2) {
+            bail!("seed line has odd number of values; expected pairs");
+        }
+
+        self.seeds
+            .chunks_exact(2)
+            .filter(|pair| pair[1] > 0)
+            .map(|pair| {
+                let end = pair[0].checked_add(pair[1]).with_context(|| {
+                    format!("seed range {} {} overflows u64", pair[0], pair[1])
+                })?;
+
+                Ok(pair[0]..end)
+            })
+            .collect()
+    }
+
+    /// Passes all seed ranges through every map section in order, returning
+    /// the resulting location ranges, sorted and merged.
+    pub fn location_ranges(&self) -> Result<Vec<Range>> {
+        Ok(merge(self.mapped_seed_ranges()?))
+    }
+
+    /// Returns the seeds (from the seed ranges) that end up at location `loc`,
+    /// as sorted and merged ranges.
+    pub fn seeds_for_location(&self, loc: u64) -> Result<Vec<Range>> {
+        // No range can reach past `u64::MAX`, so no seed ends up there
+        let Some(end) = loc.checked_add(1) else {
+            return Ok(Vec::new());
+        };
+
+        let location = loc..end;
+        let sources = self
+            .pipeline
+            .sections
+            .iter()
+            .rev()
+            .fold(vec![location], |ranges, section| {
+                section.unmap_ranges(&ranges)
+            });
+
+        let seed_ranges = self.seed_ranges()?;
+        let seeds = sources
+            .iter()
+            .flat_map(|source| {
+                seed_ranges.iter().map(|seeds| {
+                    source.start.max(seeds.start)..source.end.min(seeds.end)
+                })
+            })
+            .filter(|range| !range.is_empty())
+            .collect();
+
+        Ok(merge(seeds))
+    }
+
+    /// Passes all seed ranges through every map section in order, returning
+    /// the resulting location ranges as-is: one per mapped piece, unsorted, and
+    /// possibly overlapping if several seeds end up at the same location. Empty
+    /// pieces are left out.
+    fn mapped_seed_ranges(&self) -> Result<Vec<Range>> {
+        Ok(self
+            .seed_ranges()?
+            .into_iter()
+            .flat_map(|range| self.pipeline.map_range(range))
+            .filter(|range| !range.is_empty())
+            .collect())
+    }
+
+    /// Returns the first location `>= start` that no seed (from the seed
+    /// ranges) maps to.
+    ///
+    /// The locations from `start` on are mapped back through every map
+    /// section down to seed numbers, keeping track of how far each piece gets
+    /// shifted on the way, so the ones landing in a seed range can be shifted
+    /// back up into the locations that have seeds.
+    ///
+    /// No range can reach `u64::MAX`, so the search stops just short of it.
+    /// None if every location from `start` up to there has a seed, or if the
+    /// seeds can't be read as ranges (see `seed_ranges`).
+    pub fn first_unmapped_location(&self, start: u64) -> Option<u64> {
+        let seed_ranges = self.seed_ranges().ok()?;
+
+        let locations = start..u64::MAX;
+        let sources = self
+            .pipeline
+            .sections
+            .iter()
+            .rev()
+            .fold(vec![(locations, 0)], |pieces, section| {
+                section.unmap_shifted(&pieces)
+            });
+
+        // The locations the seeds map to, sorted and merged
+        let seeded = sources
+            .iter()
+            .flat_map(|(source, shift)| {
+                seed_ranges.iter().filter_map(move |seeds| {
+                    let start = source.start.max(seeds.start);
+                    let end = source.end.min(seeds.end);
+
+                    // Within `source`, so shifting lands back on locations
+                    (start < end).then(|| {
+                        ((start as i128 + shift) as u64)
+                            ..((end as i128 + shift) as u64)
+                    })
+                })
+            })
+            .collect();
+
+        // All of these are past `start`, so as soon as one begins after
+        // `location`, there's a gap at `location`.
+        let mut location = start;
+        for range in merge(seeded) {
+            if range.start > location {
+                break;
+            }
+
+            location = location.max(range.end);
+        }
+
+        (location < u64::MAX).then_some(location)
+    }
+
+    /// Returns each map section's name (in pipeline order) along with how many
+    /// range maps it has.
+    pub fn section_summary(&self) -> Vec<(String, usize)> {
+        self.pipeline
+            .sections
+            .iter()
+            .map(|section| (section.name.clone(), section.ranges.len()))
+            .collect()
+    }
+
+    /// Appends the map sections of `other` after this almanac's, keeping this
+    /// almanac's seeds, so seeds go through both pipelines one after the other.
+    ///
+    /// Errors if `other` doesn't start from the category this almanac ends at.
+    pub fn chain(mut self, other: Almanac) -> Result<Almanac> {
+        let end = self
+            .pipeline
+            .sections
+            .last()
+            .and_then(|section| section.name.split_once("-to-"))
+            .map(|(_, destination)| destination);
+        let start = other
+            .pipeline
+            .sections
+            .first()
+            .and_then(|section| section.name.split_once("-to-"))
+            .map(|(source, _)| source);
+
+        if let (Some(end), Some(start)) = (end, start) {
+            if end != start {
+                bail!(
+                    "cannot chain an almanac ending at {:?} with one \
+                     starting from {:?}",
+                    end,
+                    start
+                );
+            }
+        }
+
+        self.pipeline.sections.extend(other.pipeline.sections);
+        Ok(self)
+    }
+
+    /// Returns, for each map section, the source ranges covered by its range
+    /// maps, sorted with overlapping ones merged. Source numbers outside these
+    /// ranges map to themselves.
+    ///
+    /// Ranges that merely touch are kept apart, so gaps and seams between
+    /// range maps are easy to spot.
+    pub fn coverage(&self) -> Vec<(String, Vec<Range>)> {
+        self.pipeline
+            .sections
+            .iter()
+            .map(|section| {
+                let ranges = section
+                    .ranges
+                    .iter()
+                    .map(|range_map| {
+                        range_map.src_start
+                            ..(range_map.src_start + range_map.range_len)
+                    })
+                    .collect();
+
+                (section.name.clone(), merge_overlapping(ranges))
+            })
+            .collect()
+    }
+
+    /// Encodes the almanac into a compact binary form.
+    ///
+    /// Every number is written as a little-endian `u64`, and every list (and
+    /// section name) is prefixed by its length:
+    /// ```text
+    /// [seed count] [seeds...]
+    /// [section count] ([name len] [name bytes] [range count] [ranges...])...
+    /// ```
+    /// where each range is written as `src_start`, `dest_start`, `range_len`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend((self.seeds.len() as u64).to_le_bytes());
+        for seed in &self.seeds {
+            bytes.extend(seed.to_le_bytes());
+        }
+
+        bytes.extend((self.pipeline.sections.len() as u64).to_le_bytes());
+        for section in &self.pipeline.sections {
+            bytes.extend((section.name.len() as u64).to_le_bytes());
+            bytes.extend(section.name.as_bytes());
+
+            bytes.extend((section.ranges.len() as u64).to_le_bytes());
+            for range_map in &section.ranges {
+                bytes.extend(range_map.src_start.to_le_bytes());
+                bytes.extend(range_map.dest_start.to_le_bytes());
+                bytes.extend(range_map.range_len.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Decodes an almanac previously encoded with [`Almanac::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader { bytes };
+
+        let seed_count = reader.read_len()?;
+        let seeds = (0..seed_count)
+            .map(|_| reader.read_u64())
+            .collect::<Result<Vec<_>>>()?;
+
+        let section_count = reader.read_len()?;
+        let mut sections = Vec::new();
+        for _ in 0..section_count {
+            let name_len = reader.read_len()?;
+            let name = String::from_utf8(reader.read_bytes(name_len)?.to_vec())
+                .context("section name is not valid UTF-8")?;
+
+            let range_count = reader.read_len()?;
+            let ranges = (0..range_count)
+                .map(|_| {
+                    RangeMap::try_new(
+                        reader.read_u64()?,
+                        reader.read_u64()?,
+                        reader.read_u64()?,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            sections.push(Section { name, ranges });
+        }
+
+        if !reader.bytes.is_empty() {
+            bail!("{} trailing bytes after almanac", reader.bytes.len());
+        }
+
+        Ok(Self {
+            seeds,
+            pipeline: Pipeline { sections },
+        })
+    }
+}
+
+/// Cursor over the bytes of an encoded almanac.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            bail!("unexpected end of almanac bytes");
+        }
+
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(head)
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let bytes = self.read_bytes(8)?;
+
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        Ok(self.read_u64()?.try_into()?)
+    }
+}
+
+/// Parses the list of seeds.
+///
+/// `"seeds: 79 14 55 13"` -> `[79, 14, 55, 13]`
+fn parse_seeds(input: &str) -> IResult<&str, Vec<u64>> {
+    preceded(
+        tag("seeds:"),
+        preceded(space1, separated_list1(space1, u64)),
+    )(input)
+}
+
+/// Makes sure the almanac starts with a `seeds:` line listing at least one
+/// number, so the parsers don't go looking for seeds further down the input.
+fn check_seeds_line(input: &str) -> Result<()> {
+    let line = input.lines().next().unwrap_or_default();
+
+    let Some(seeds) = line.strip_prefix("seeds:") else {
+        bail!("expected the first line to start with \"seeds:\", got {line:?}");
+    };
+
+    if seeds.split_whitespace().next().is_none() {
+        bail!("seeds line lists no seed numbers");
+    }
+
+    if let Some(bad) =
+        seeds.split_whitespace().find(|n| n.parse::<u64>().is_err())
+    {
+        bail!("invalid seed number {bad:?} on the seeds line");
+    }
+
+    Ok(())
+}
+
+/// Puts the sections in pipeline order by following their `x-to-y` names from
+/// `seed` (normally down to `location`), whatever order they were written in.
+/// Without a section from `seed`, the chain starts from the one category that
+/// nothing maps to instead (e.g. `location` for a stage meant to be chained
+/// after another almanac).
+///
+/// Errors if any section can't be reached that way.
+fn route(sections: Vec<Section>) -> Result<Vec<Section>> {
+    // Mapping of each section's source category to its destination category
+    // and the section itself
+    let mut by_source = HashMap::new();
+    for section in sections {
+        let (source, destination) =
+            section.name.split_once("-to-").with_context(|| {
+                format!("invalid section name {:?}", section.name)
+            })?;
+        let (source, destination) =
+            (source.to_string(), destination.to_string());
+
+        if by_source.contains_key(&source) {
+            bail!("more than one map section from {:?}", source);
+        }
+        by_source.insert(source, (destination, section));
+    }
+
+    let mut category = "seed".to_string();
+    if !by_source.contains_key(&category) {
+        let mut heads = by_source.keys().filter(|source| {
+            by_source
+                .values()
+                .all(|(destination, _)| destination != *source)
+        });
+
+        match (heads.next(), heads.next()) {
+            (Some(head), None) => category = head.clone(),
+            _ => bail!("no map section from \"seed\" to start from"),
+        }
+    }
+
+    // Follow the chain for as long as it goes (normally down to location)
+    let mut routed = Vec::new();
+    while let Some((destination, section)) = by_source.remove(&category) {
+        routed.push(section);
+        category = destination;
+    }
+
+    if let Some(source) = by_source.keys().next() {
+        bail!("map section from {:?} is not reachable from seed", source);
+    }
+
+    Ok(routed)
+}
+
+/// Parses a map section along with its name.
+///
+/// Example:
+/// ```text
+/// seed-to-soil map:
+/// 50 98 2
+/// 52 50 48
+/// ```
+/// becomes `Section { "seed-to-soil", [RangeMap {98, 50, 2}, RangeMap {50, 52, 48}] }`.
+fn parse_section(input: &str) -> IResult<&str, Section> {
+    let (input, name) = terminated(take_until1(" map:"), tag(" map:"))(input)?;
+    let (input, _) = line_ending(input)?;
+
+    let (input, ranges) = separated_list1(
+        line_ending,
+        map_res(
+            tuple((terminated(u64, space1), terminated(u64, space1), u64)),
+            |(dest_start, src_start, range_len)| {
+                RangeMap::try_new(src_start, dest_start, range_len)
+            },
+        ),
+    )(input)?;
+
+    Ok((
+        input,
+        Section {
+            name: name.to_string(),
+            ranges,
+        },
+    ))
 }
 
 /// The naive brute-force solution to part 1.
@@ -37,202 +791,190 @@ impl RangeMap {
 pub mod part1 {
     use super::*;
 
+    /// Finds the lowest location of all seeds. With the `parallel` feature (or
+    /// its `rayon` alias) on, the seeds are mapped across rayon's thread pool;
+    /// the serial path is the default.
     pub fn run(input: &str) -> Result<String> {
-        let (input, seeds) =
-            terminated(parse_seeds, multispace1)(input).unwrap();
+        let almanac = Almanac::parse(input)?;
 
-        let (_, min_location) = parse_min_location(input, &seeds).unwrap();
+        // Map every seed to its location number, then find the smallest one
+        #[cfg(feature = "parallel")]
+        let min_location = min_location_parallel(&almanac);
+        #[cfg(not(feature = "parallel"))]
+        let min_location = min_location_serial(&almanac);
 
-        Ok(min_location.to_string())
+        Ok(min_location.context("almanac has no seeds")?.to_string())
     }
 
-    /// Parses the list of seeds.
-    ///
-    /// `"seeds: 79 14 55 13"` -> `[79, 14, 55, 13]`
-    fn parse_seeds(input: &str) -> IResult<&str, Vec<u64>> {
-        preceded(
-            preceded(take_until1(": "), tag(": ")),
-            separated_list1(space1, u64),
-        )(input)
-    }
-
-    /// Parses the lowest location number for the given list of seeds.
-    fn parse_min_location<'a>(
-        input: &'a str,
-        seeds: &[u64],
-    ) -> IResult<&'a str, u64> {
-        let (input, map_sections) =
-            separated_list1(tag("\n\n"), parse_map)(input)?;
-
-        // Gather the location numbers of all seeds, then find the smallest one
-        let min_location_num = seeds
+    /// Finds the lowest location number of all seeds, one seed at a time.
+    #[cfg(any(test, not(feature = "parallel")))]
+    pub(super) fn min_location_serial(almanac: &Almanac) -> Option<u64> {
+        almanac
+            .seeds()
             .iter()
-            .map(|seed| {
-                let mut num = *seed;
-
-                // For each seed, we pass it through the map pipeline one by one
-                // till the last one to obtain the location number.
-                for map_section in &map_sections {
-                    num = *map_section
-                        .iter()
-                        .filter_map(|range_map| range_map.map(num))
-                        .collect::<Vec<_>>()
-                        // If `num` is in some mapped range, use the map.
-                        // Otherwise, source num == destination num.
-                        .first()
-                        .unwrap_or(&num);
-                }
+            .map(|&seed| almanac.pipeline().map(seed))
+            .min()
+    }
 
-                num
-            })
+    /// Like `min_location_serial`, but mapping the seeds on rayon's pool.
+    #[cfg(feature = "parallel")]
+    pub(super) fn min_location_parallel(almanac: &Almanac) -> Option<u64> {
+        use rayon::prelude::*;
+
+        almanac
+            .seeds()
+            .par_iter()
+            .map(|&seed| almanac.pipeline().map(seed))
             .min()
-            .unwrap();
+    }
 
-        Ok((input, min_location_num))
+    /// Returns each seed (in order) paired with its location number.
+    pub fn seed_locations(input: &str) -> Result<Vec<(u64, u64)>> {
+        let almanac = Almanac::parse(input)?;
+
+        Ok(almanac
+            .seeds()
+            .iter()
+            .map(|&seed| (seed, almanac.pipeline().map(seed)))
+            .collect())
     }
 
-    /// Parses each map section into a list of range maps.
-    ///
-    /// Example:
-    /// ```text
-    /// seed-to-soil map:
-    /// 50 98 2
-    /// 52 50 48
-    /// ```
-    /// becomes `[RangeMap {98, 50, 2}, RangeMap {50, 52, 48}]`.
-    fn parse_map(input: &str) -> IResult<&str, Vec<RangeMap>> {
-        // Ignore the first line of the section, e.g. "seed-to-soil map:"
-        let (input, _) =
-            preceded(take_till1(|c| c == '\n'), line_ending)(input)?;
-
-        separated_list1(
-            line_ending,
-            map(separated_list1(space1, u64), |nums| RangeMap {
-                src_start: nums[1],
-                dest_start: nums[0],
-                range_len: nums[2],
-            }),
-        )(input)
+    /// Counts how many seeds end up at each location. A count above 1 means
+    /// several seeds collide on the same location (or a seed is listed twice).
+    pub fn location_multiplicity(input: &str) -> Result<BTreeMap<u64, usize>> {
+        let mut counts = BTreeMap::new();
+        for (_, location) in seed_locations(input)? {
+            *counts.entry(location).or_insert(0) += 1;
+        }
+
+        Ok(counts)
     }
 }
 
 /// A smarter implementation for part 2 compared to part 1's naive solution.
 ///
-/// The essence is that we flip the direction of the map pipeline: instead of
-/// processing each seed top (`seed`) to bottom (`location`), we process potential
-/// location numbers in ascending order up the pipeline (`location` -> `seed`).
-/// We're trying to find the smallest location number anyways, so the first one
-/// that falls in a seed range will automatically be the answer.
-///
-/// This is still kinda brute-forcing, but instead of the cost scaling with the
-/// number of seeds (which is... gigantic for part 2), apparently the search
-/// space is smaller. Credit goes to some of the comments at the AOC subreddit
-/// for the idea :)
+/// Instead of passing each seed through the map pipeline one by one (there are
+/// way too many of them for part 2), whole seed ranges go through it at once.
+/// At each map section, a range is split into the parts covered by each range
+/// map (which get shifted) and the parts not covered by any (which stay as
+/// they are). The answer is the lowest start of the location ranges that come
+/// out the other end, so the cost scales with the number of ranges rather
+/// than the number of seeds.
 pub mod part2 {
     use super::*;
 
-    type Range = std::ops::Range<u64>;
-
     pub fn run(input: &str) -> Result<String> {
-        let (input, seed_ranges) =
-            terminated(parse_seed_ranges, multispace1)(input).unwrap();
+        let almanac = parse_almanac(input)?;
 
-        let (_, min_location) =
-            parse_min_location(input, &seed_ranges).unwrap();
+        // Carry the seed ranges through every map section, splitting them
+        // wherever range maps only cover part of them, then take the lowest
+        // start of the location ranges they end up as.
+        let min_location = almanac
+            .mapped_seed_ranges()?
+            .iter()
+            .map(|range| range.start)
+            .min()
+            .context("almanac has no seed ranges")?;
 
         Ok(min_location.to_string())
     }
 
-    /// Parses the list of seed ranges.
-    ///
-    /// `"seeds: 79 14 55 13"` -> `[79..93, 55..68]`
-    fn parse_seed_ranges(input: &str) -> IResult<&str, Vec<Range>> {
-        preceded(
-            preceded(take_until1(": "), tag(": ")),
-            separated_list1(space1, separated_pair(u64, space1, u64)),
-        )(input)
-        .map(|(input, seeds)| {
-            (
-                input,
-                seeds
-                    .iter()
-                    .map(|&(start, len)| Range {
-                        start,
-                        end: start + len,
-                    })
-                    .collect(),
-            )
-        })
+    /// Returns the highest location number reachable from the seed ranges.
+    pub fn max_location(input: &str) -> Result<u64> {
+        let almanac = parse_almanac(input)?;
+
+        // Location ranges are sorted and half-open, so the highest location is
+        // just before the end of the last range.
+        let last = almanac
+            .location_ranges()?
+            .pop()
+            .context("almanac has no seed ranges")?;
+
+        Ok(last.end - 1)
     }
 
-    /// Parses the lowest location number for the given list of seed ranges.
-    fn parse_min_location<'a>(
-        input: &'a str,
-        seed_ranges: &'a [Range],
-    ) -> IResult<&'a str, u64> {
-        let (input, mut map_sections) =
-            separated_list1(tag("\n\n"), parse_map_reversed)(input)?;
-
-        // We go through the pipeline backwards/upwards: from `location` back up
-        // to the `seed` ranges.
-        map_sections.reverse();
-
-        // We now iterate through all possible location numbers (ascending order),
-        // and the first one that falls in a seed range is the answer.
-        let min_location = (0..=u64::MAX)
-            .find(|location| {
-                let mut seed_equivalent = *location;
-
-                // For each location number, we pass it through the map pipeline
-                // upwards till the last one (the seed one).
-                for map_section in &map_sections {
-                    seed_equivalent = *map_section
-                        .iter()
-                        .filter_map(|range| range.map(seed_equivalent))
-                        .collect::<Vec<_>>()
-                        .first()
-                        .unwrap_or(&seed_equivalent);
+    /// Counts how many seeds (from the seed ranges) end up in each of
+    /// `buckets` equal-width location bins, spanning the lowest to the highest
+    /// reachable location. The last bin may be cut short.
+    ///
+    /// The bin width is rounded up, so fewer bins may be needed to cover the
+    /// span (e.g. a span of 10 in 6 bins takes 5 bins of 2). Only those are
+    /// returned, rather than trailing bins starting past the highest location.
+    pub fn location_histogram(input: &str, buckets: usize) -> Result<Vec<u64>> {
+        if buckets == 0 {
+            bail!("need at least one bucket");
+        }
+
+        let ranges = parse_almanac(input)?.mapped_seed_ranges()?;
+
+        let min = ranges
+            .iter()
+            .map(|range| range.start)
+            .min()
+            .context("almanac has no seed ranges")?;
+        let max = ranges.iter().map(|range| range.end).max().unwrap_or(min);
+
+        // u128 so a span of every possible u64 still fits
+        let span = (max - min) as u128;
+        let width = span.div_ceil(buckets as u128).max(1);
+        let buckets = span.div_ceil(width).max(1) as usize;
+
+        let mut histogram = vec![0; buckets];
+        for range in ranges {
+            let (start, end) =
+                ((range.start - min) as u128, (range.end - min) as u128);
+
+            // Only the bins the range overlaps need looking at
+            for (i, count) in histogram
+                .iter_mut()
+                .enumerate()
+                .skip((start / width) as usize)
+            {
+                let bin_start = i as u128 * width;
+                if bin_start >= end {
+                    break;
                 }
 
-                // If this location's "seed equivalent" number falls in any seed
-                // range, it is the answer.
-                seed_ranges
+                let overlap = end.min(bin_start + width) - start.max(bin_start);
+                *count += overlap as u64;
+            }
+        }
+
+        Ok(histogram)
+    }
+
+    /// Finds the lowest location by mapping each seed range through the map
+    /// sections on its own thread (from rayon's pool), then taking the lowest
+    /// location of them all.
+    #[cfg(feature = "parallel")]
+    pub fn solve_parallel(input: &str) -> Result<u64> {
+        use rayon::prelude::*;
+
+        let almanac = parse_almanac(input)?;
+
+        almanac
+            .seed_ranges()?
+            .into_par_iter()
+            .filter_map(|range| {
+                almanac
+                    .pipeline()
+                    .map_range(range)
                     .iter()
-                    .any(|seed_range| seed_range.contains(&seed_equivalent))
+                    .filter(|range| !range.is_empty())
+                    .map(|range| range.start)
+                    .min()
             })
-            .unwrap();
-
-        Ok((input, min_location))
+            .min()
+            .context("almanac has no seed ranges")
     }
 
-    /// Parses each map section into a list of range maps.
-    ///
-    /// Different to part 1, we're considering the first number to be the source
-    /// instead, and the second number the destination start. This is because
-    /// we're going to be mapping the sections backwards/upwards
-    /// (`location` -> `seed`).
-    ///
-    /// Example:
-    /// ```text
-    /// seed-to-soil map:
-    /// 50 98 2
-    /// 52 50 48
-    /// ```
-    /// becomes `[RangeMap {50, 98, 2}, RangeMap {52, 50, 48}]`.
-    fn parse_map_reversed(input: &str) -> IResult<&str, Vec<RangeMap>> {
-        // Ignore the first line of the map section, e.g. "seed-to-soil map:"
-        let (input, _) =
-            preceded(take_till1(|c| c == '\n'), line_ending)(input)?;
-
-        separated_list1(
-            line_ending,
-            map(separated_list1(space1, u64), |nums| RangeMap {
-                // First number is source; second destination
-                src_start: nums[0],
-                dest_start: nums[1],
-                range_len: nums[2],
-            }),
-        )(input)
+    /// Parses the almanac, making sure its seed numbers can be read as pairs of
+    /// range start and range length.
+    fn parse_almanac(input: &str) -> Result<Almanac> {
+        let almanac = Almanac::parse(input)?;
+        almanac.seed_ranges()?;
+
+        Ok(almanac)
     }
 }
 
@@ -317,4 +1059,845 @@ humidity-to-location map:
 
         assert_eq!("46", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn almanac_bytes_round_trip_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let almanac = Almanac::parse(input).unwrap();
+        let restored = Almanac::from_bytes(&almanac.to_bytes()).unwrap();
+
+        assert_eq!(almanac.seeds(), restored.seeds());
+        for &seed in almanac.seeds() {
+            assert_eq!(
+                almanac.seed_to_location(seed),
+                restored.seed_to_location(seed)
+            );
+        }
+    }
+
+    #[test]
+    fn part1_seed_locations_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        assert_eq!(
+            vec![(79, 82), (14, 43), (55, 86), (13, 35)],
+            part1::seed_locations(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn almanac_first_unmapped_location_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let almanac = Almanac::parse(input).unwrap();
+
+        assert_eq!(Some(0), almanac.first_unmapped_location(0));
+        assert_eq!(Some(61), almanac.first_unmapped_location(46));
+        assert_eq!(Some(85), almanac.first_unmapped_location(82));
+
+        // Every location below u64::MAX has a seed
+        let input = "seeds: 0 18446744073709551615
+
+seed-to-location map:
+1 0 1
+0 1 1
+";
+        let almanac = Almanac::parse(input).unwrap();
+
+        assert_eq!(None, almanac.first_unmapped_location(0));
+    }
+
+    #[test]
+    fn part2_max_location_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        assert_eq!(98, part2::max_location(input).unwrap());
+    }
+
+    #[test]
+    fn missing_seeds_line_err() {
+        let input = "seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37";
+
+        let err = Almanac::parse(input).err().unwrap();
+        assert_eq!(
+            "expected the first line to start with \"seeds:\", got \"seed-to-soil map:\"",
+            err.to_string()
+        );
+        assert!(part1::run(input).is_err());
+        assert!(part2::run(input).is_err());
+
+        let err = Almanac::parse("seeds:\n\nseed-to-soil map:\n50 98 2")
+            .err()
+            .unwrap();
+        assert_eq!("seeds line lists no seed numbers", err.to_string());
+    }
+
+    #[test]
+    fn almanac_coverage_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15";
+
+        let coverage = Almanac::parse(input).unwrap().coverage();
+
+        assert_eq!(
+            vec![
+                ("seed-to-soil".to_string(), vec![50..98, 98..100]),
+                (
+                    "soil-to-fertilizer".to_string(),
+                    vec![0..15, 15..52, 52..54]
+                ),
+            ],
+            coverage
+        );
+    }
+
+    #[test]
+    fn part2_location_histogram_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let histogram = part2::location_histogram(input, 4).unwrap();
+
+        assert_eq!(14 + 13, histogram.iter().sum::<u64>());
+        assert_eq!(vec![14, 1, 5, 7], histogram);
+        assert!(part2::location_histogram(input, 0).is_err());
+
+        // Locations 0..10 in bins 2 wide, with no bin past location 9
+        let input = "seeds: 0 10
+
+seed-to-location map:
+100 100 1
+";
+
+        assert_eq!(
+            vec![2, 2, 2, 2, 2],
+            part2::location_histogram(input, 6).unwrap()
+        );
+        assert_eq!(
+            vec![3, 3, 3, 1],
+            part2::location_histogram(input, 4).unwrap()
+        );
+    }
+
+    #[test]
+    fn almanac_map_from_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let almanac = Almanac::parse(input).unwrap();
+
+        // Seed 79 -> soil 81 -> ... -> location 82
+        assert_eq!(82, almanac.map_from("soil", 81).unwrap());
+        assert_eq!(82, almanac.map_from("seed", 79).unwrap());
+        assert_eq!(5, almanac.map_from("location", 5).unwrap());
+        assert!(almanac.map_from("gravel", 5).is_err());
+    }
+
+    #[test]
+    fn shuffled_sections_ok() {
+        let input = "seeds: 79 14 55 13
+
+humidity-to-location map:
+60 56 37
+56 93 4
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+water-to-light map:
+88 18 7
+18 25 70
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4";
+
+        assert_eq!("35", part1::run(input).unwrap());
+        assert_eq!(
+            46,
+            Almanac::parse(input).unwrap().location_ranges().unwrap()[0].start
+        );
+
+        // Dropping a section leaves the ones after it unreachable
+        let end = input.find("\n\nfertilizer-to-water").unwrap();
+        assert!(Almanac::parse(&input[..end]).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn part2_solve_parallel_ok() {
+        let input = "seeds: 79 14 55 13 0 5 90 3 20 30
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let sequential =
+            Almanac::parse(input).unwrap().location_ranges().unwrap()[0].start;
+        assert_eq!(sequential, part2::solve_parallel(input).unwrap());
+    }
+
+    #[test]
+    fn part2_odd_seed_count_err() {
+        let input = "seeds: 79 14 55 13 7
+
+seed-to-soil map:
+50 98 2
+52 50 48";
+
+        let expected = "seed line has odd number of values; expected pairs";
+        assert_eq!(expected, part2::run(input).unwrap_err().to_string());
+        assert_eq!(
+            expected,
+            part2::max_location(input).unwrap_err().to_string()
+        );
+
+        // Part 1 reads the seeds one by one, so any count is fine there
+        assert!(part1::run(input).is_ok());
+    }
+
+    #[test]
+    fn almanac_section_summary_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let summary = Almanac::parse(input).unwrap().section_summary();
+
+        assert_eq!(
+            vec![
+                ("seed-to-soil".to_string(), 2),
+                ("soil-to-fertilizer".to_string(), 3),
+                ("fertilizer-to-water".to_string(), 4),
+                ("water-to-light".to_string(), 2),
+                ("light-to-temperature".to_string(), 3),
+                ("temperature-to-humidity".to_string(), 2),
+                ("humidity-to-location".to_string(), 2),
+            ],
+            summary
+        );
+    }
+
+    #[test]
+    fn almanac_seeds_for_location_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let almanac = Almanac::parse(input).unwrap();
+
+        assert_eq!(vec![82..83], almanac.seeds_for_location(46).unwrap());
+        assert!(almanac.seeds_for_location(0).unwrap().is_empty());
+        assert!(almanac.seeds_for_location(u64::MAX).unwrap().is_empty());
+    }
+
+    #[test]
+    fn range_map_try_new_err() {
+        assert!(RangeMap::try_new(98, 50, 2).is_ok());
+        assert!(RangeMap::try_new(98, 50, 0).is_err());
+        assert!(RangeMap::try_new(u64::MAX - 1, 50, 2).is_err());
+        assert!(RangeMap::try_new(50, u64::MAX, 1).is_err());
+
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 0";
+
+        assert!(Almanac::parse(input).is_err());
+    }
+
+    #[test]
+    fn part1_location_multiplicity_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        assert_eq!(
+            BTreeMap::from([(35, 1), (43, 1), (82, 1), (86, 1)]),
+            part1::location_multiplicity(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn almanac_chain_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+        let identity = "seeds: 0
+
+location-to-place map:
+0 0 100";
+
+        let almanac = Almanac::parse(input).unwrap();
+        let chained = Almanac::parse(input)
+            .unwrap()
+            .chain(Almanac::parse(identity).unwrap())
+            .unwrap();
+
+        assert_eq!(8, chained.section_summary().len());
+        for &seed in almanac.seeds() {
+            assert_eq!(
+                almanac.seed_to_location(seed),
+                chained.seed_to_location(seed)
+            );
+        }
+
+        // The sample starts from seed, not location
+        let mismatched = Almanac::parse(input)
+            .unwrap()
+            .chain(Almanac::parse(input).unwrap());
+        assert!(mismatched.is_err());
+    }
+
+    #[test]
+    fn part2_large_seed_range_ok() {
+        // A billion seeds, none of which end up anywhere below 3 billion
+        let input = "seeds: 3000000000 1000000000
+
+seed-to-location map:
+5000000000 3000000000 1000";
+
+        assert_eq!("3000001000", part2::run(input).unwrap());
+    }
+
+    #[test]
+    fn range_near_u64_max_ok() {
+        // Seeds right at the top of the u64 range, with a range map ending
+        // exactly at u64::MAX
+        let input = "seeds: 18446744073709551614 1
+
+seed-to-location map:
+0 18446744073709551614 1";
+
+        assert_eq!("0", part1::run(input).unwrap());
+        assert_eq!("0", part2::run(input).unwrap());
+
+        // One more and the source range would end past u64::MAX
+        let input = "seeds: 18446744073709551614 1
+
+seed-to-location map:
+0 18446744073709551614 2";
+
+        assert!(part1::run(input).is_err());
+        assert!(part2::run(input).is_err());
+    }
+
+    #[test]
+    fn pipeline_ok() {
+        let input = "seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let pipeline: Pipeline = input.parse().unwrap();
+
+        assert_eq!(82, pipeline.map(79));
+        assert_eq!(
+            46,
+            pipeline
+                .map_range(79..93)
+                .iter()
+                .map(|range| range.start)
+                .min()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn part1_min_location_parallel_ok() {
+        let input = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+        let almanac = Almanac::parse(input).unwrap();
+
+        assert_eq!(Some(35), part1::min_location_parallel(&almanac));
+        assert_eq!(
+            part1::min_location_serial(&almanac),
+            part1::min_location_parallel(&almanac)
+        );
+    }
+
+    #[test]
+    fn part2_max_location_empty_err() {
+        let input = "seeds: 0 0
+
+seed-to-soil map:
+50 98 2
+";
+
+        assert!(Almanac::parse(input)
+            .unwrap()
+            .seed_ranges()
+            .unwrap()
+            .is_empty());
+        assert!(part2::max_location(input).is_err());
+        assert!(part2::run(input).is_err());
+    }
+
+    #[test]
+    fn pipeline_trailing_input_err() {
+        let input = "seed-to-soil map:
+50 98 2
+
+oops";
+
+        let err = input.parse::<Pipeline>().err().unwrap();
+        assert_eq!(
+            "unexpected input after the map sections: \"oops\"",
+            err.to_string()
+        );
+        assert!("seed-to-soil map:\n50 98 2\n\n".parse::<Pipeline>().is_ok());
+    }
+
+    #[test]
+    fn seed_ranges_err() {
+        let input = "seeds: 18446744073709551615 1
+
+seed-to-soil map:
+50 98 2
+";
+
+        let err = Almanac::parse(input).unwrap().seed_ranges().unwrap_err();
+        assert_eq!(
+            "seed range 18446744073709551615 1 overflows u64",
+            err.to_string()
+        );
+        assert!(part2::run(input).is_err());
+
+        let input = "seeds: 79 14 55
+
+seed-to-soil map:
+50 98 2
+";
+
+        assert!(Almanac::parse(input).unwrap().seed_ranges().is_err());
+    }
 }
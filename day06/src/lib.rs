@@ -1,18 +1,118 @@
-use anyhow::Result;
-use nom::bytes::complete::{tag, take_until1};
-use nom::character::complete::{digit1, space1, u64};
-use nom::multi::separated_list1;
-use nom::sequence::preceded;
-use nom::IResult;
+use anyhow::{anyhow, Context, Result};
+use nom::bytes::complete::tag;
+use nom::character::complete::{
+    digit1, line_ending, not_line_ending, space1, u64,
+};
+use nom::combinator::verify;
+use nom::multi::{many_till, separated_list1};
+use nom::number::complete::double;
+use nom::sequence::{preceded, terminated};
+use nom::{IResult, Parser};
 
+/// A race, given by its time limit and the record distance to beat.
 #[derive(Debug, PartialEq)]
-struct Race {
-    time: u64,
-    dist: u64,
+pub struct Race {
+    pub time: u64,
+    pub dist: u64,
+}
+
+impl From<(u64, u64)> for Race {
+    /// Makes a race from a `(time, dist)` pair.
+    fn from((time, dist): (u64, u64)) -> Self {
+        Race { time, dist }
+    }
+}
+
+/// One or more races to be solved together by `solve`.
+#[derive(Debug, PartialEq)]
+pub struct Races(Vec<Race>);
+
+impl From<Vec<Race>> for Races {
+    fn from(races: Vec<Race>) -> Self {
+        Races(races)
+    }
+}
+
+impl From<Race> for Races {
+    fn from(race: Race) -> Self {
+        Races(vec![race])
+    }
+}
+
+impl From<(u64, u64)> for Races {
+    fn from(race: (u64, u64)) -> Self {
+        Races(vec![race.into()])
+    }
+}
+
+/// Multiplies the number of ways to win each of the given races, which can be
+/// a `(time, dist)` pair, a single `Race` or a list of them.
+///
+/// Errors if the product overflows `u64`.
+pub fn solve<T: Into<Races>>(races: T) -> Result<u64> {
+    let Races(races) = races.into();
+
+    races.iter().try_fold(1_u64, |acc, race| {
+        acc.checked_mul(race.ways_to_win())
+            .context("product of ways to win overflows u64")
+    })
+}
+
+/// Races with a time up to this are solved by counting the winning hold times
+/// one by one; anything longer is solved in O(1) with the quadratic formula.
+const NAIVE_THRESHOLD: u64 = 10_000;
+
+/// The method used to count the ways to win a race.
+#[derive(Debug, PartialEq)]
+enum Solver {
+    Naive,
+    Quadratic,
 }
 
 impl Race {
     fn ways_to_win(&self) -> u64 {
+        if !self.is_winnable() {
+            return 0;
+        }
+
+        match self.solver() {
+            Solver::Naive => self.ways_to_win_naive(),
+            Solver::Quadratic => self.ways_to_win_fast(),
+        }
+    }
+
+    /// Counts the ways to win when the button has already been held for
+    /// `offset` ms, i.e. only hold times in `offset..=time` are considered.
+    pub fn ways_to_win_with_offset(&self, offset: u64) -> u64 {
+        self.winning_bounds().map_or(0, |(lowest, highest)| {
+            (highest + 1).saturating_sub(lowest.max(offset))
+        })
+    }
+
+    /// Returns whether any hold time beats the record.
+    ///
+    /// The distance peaks when holding for half the race time, so it's enough
+    /// to check whether that beats the record.
+    pub fn is_winnable(&self) -> bool {
+        let (time, dist) = (self.time as u128, self.dist as u128);
+        let hold = time / 2;
+
+        hold * (time - hold) > dist
+    }
+
+    /// Picks the method `ways_to_win` uses for this race.
+    ///
+    /// Counting is simple and cache-friendly for short races, but its cost
+    /// scales with the race time, so longer races go through the formula.
+    fn solver(&self) -> Solver {
+        if self.time <= NAIVE_THRESHOLD {
+            Solver::Naive
+        } else {
+            Solver::Quadratic
+        }
+    }
+
+    fn ways_to_win_naive(&self) -> u64 {
         // Naive way — iterating through each possibility one by one, filtering
         // the ones that win.
         // return (0..=self.time)
@@ -46,40 +146,239 @@ impl Race {
         // If the time of the race is even, the middle (`time / 2`) stands on
         // its own when split by half, so we minus 1 (since we `counter += 2`
         // each time previously).
-        if self.time % 2 == 0 {
+        if self.time.is_multiple_of(2) && counter > 0 {
             counter - 1
         } else {
             counter
         }
     }
+
+    fn ways_to_win_fast(&self) -> u64 {
+        self.winning_bounds()
+            .map_or(0, |(lowest, highest)| highest - lowest + 1)
+    }
+
+    /// Returns the lowest and highest winning hold times (every hold in
+    /// between wins too), None if the race can't be won.
+    pub fn winning_bounds(&self) -> Option<(u64, u64)> {
+        // Holding for `h` wins when `h * (time - h) > dist`, i.e. when `h` lies
+        // strictly between the roots of `h^2 - time*h + dist = 0`:
+        // h = (time ± sqrt(time^2 - 4*dist)) / 2
+        // Everything is done in integers (u128 so the squares can't overflow)
+        // to avoid any floating point rounding issues.
+        let (time, dist) = (self.time as u128, self.dist as u128);
+        let discriminant = (time * time).checked_sub(4 * dist)?;
+
+        // Rearranged, `h` wins when `|time - 2h| < sqrt(discriminant)`. For
+        // whole numbers that's `|time - 2h| <= reach`, where `reach` is the
+        // square root rounded down, minus one if the root is exact (as landing
+        // exactly on a root only ties the record).
+        let root = discriminant.isqrt();
+        let reach = if root * root == discriminant {
+            root.checked_sub(1)?
+        } else {
+            root
+        };
+
+        // time - reach <= 2h <= time + reach, rounded inward
+        let lowest = (time - reach).div_ceil(2);
+        let highest = (time + reach) / 2;
+        if lowest > highest {
+            return None;
+        }
+
+        debug_assert!(lowest * (time - lowest) > dist);
+        debug_assert!(highest * (time - highest) > dist);
+
+        Some((lowest as u64, highest as u64))
+    }
+}
+
+/// How many holds `DecimalRace::ways_to_win` may move each estimated root by.
+const MAX_NUDGES: usize = 2;
+
+/// A race whose time and record distance may be fractional. Hold times are
+/// still whole milliseconds.
+#[derive(Debug, PartialEq)]
+struct DecimalRace {
+    time: f64,
+    dist: f64,
+}
+
+impl DecimalRace {
+    /// Counts the whole hold times that beat the record.
+    fn ways_to_win(&self) -> u64 {
+        // Same as `Race::ways_to_win_fast`: winning holds lie strictly between
+        // the roots of `h^2 - time*h + dist = 0`.
+        let wins = |hold: f64| hold * (self.time - hold) > self.dist;
+
+        let discriminant = self.time * self.time - 4.0 * self.dist;
+        if discriminant < 0.0 || self.time < 0.0 {
+            return 0;
+        }
+
+        let root = discriminant.sqrt();
+        let max_hold = self.time.floor();
+
+        // Floating point rounding might put these a hold off either way, so
+        // nudge them into place. The nudges are capped, as past 2^53 adding
+        // 1.0 no longer changes the value and they'd never settle.
+        let mut lowest = ((self.time - root) / 2.0).floor().max(0.0);
+        for _ in 0..MAX_NUDGES {
+            if lowest <= max_hold && !wins(lowest) {
+                lowest += 1.0;
+            }
+        }
+        for _ in 0..MAX_NUDGES {
+            if lowest > 0.0 && wins(lowest - 1.0) {
+                lowest -= 1.0;
+            }
+        }
+
+        let mut highest = ((self.time + root) / 2.0).ceil().min(max_hold);
+        for _ in 0..MAX_NUDGES {
+            if highest >= lowest && !wins(highest) {
+                highest -= 1.0;
+            }
+        }
+        for _ in 0..MAX_NUDGES {
+            if highest < max_hold && wins(highest + 1.0) {
+                highest += 1.0;
+            }
+        }
+
+        if lowest > highest || !wins(lowest) || !wins(highest) {
+            0
+        } else {
+            (highest - lowest) as u64 + 1
+        }
+    }
+}
+
+/// Returns, for each race, the fraction of hold times (from 0 to the race time,
+/// both included) that beat the record, i.e. the chance that holding for a
+/// random whole number of milliseconds wins.
+pub fn win_fractions(input: &str) -> Result<Vec<f64>> {
+    let (_, races) = part1::parse_races(input)
+        .map_err(|e| anyhow!("failed to parse races: {e}"))?;
+
+    Ok(races
+        .iter()
+        .map(|race| race.ways_to_win() as f64 / (race.time as f64 + 1.0))
+        .collect())
+}
+
+/// Returns the number of ways to win the single race read with its digits run
+/// together (part 2), along with the product of the ways to win each race read
+/// separately (part 1).
+pub fn kerned_vs_individual(input: &str) -> Result<(u64, u64)> {
+    let (_, races) = part1::parse_races(input)
+        .map_err(|e| anyhow!("failed to parse races: {e}"))?;
+    let (_, race) = part2::parse_race(input)
+        .map_err(|e| anyhow!("failed to parse race: {e}"))?;
+
+    Ok((race.ways_to_win(), solve(races)?))
+}
+
+/// Returns the race with the fewest ways to win, along with that number of
+/// ways. Ties go to the shorter race.
+pub fn tightest_race(input: &str) -> Result<(Race, u64)> {
+    let (_, races) = part1::parse_races(input)
+        .map_err(|e| anyhow!("failed to parse races: {e}"))?;
+
+    races
+        .into_iter()
+        .map(|race| {
+            let ways = race.ways_to_win();
+            (race, ways)
+        })
+        .min_by_key(|(race, ways)| (*ways, race.time))
+        .context("no races in input")
+}
+
+/// Applies `parser` to the contents of the line starting with `label` (e.g.
+/// "Time:"), skipping any other lines before it.
+///
+/// Each labelled line is looked for from the start of the input, so lines can
+/// come in any order and be surrounded by unrelated ones (like a title line).
+fn labelled<'a, O, F>(
+    label: &'static str,
+    parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O>
+where
+    F: Parser<&'a str, O, nom::error::Error<&'a str>>,
+{
+    preceded(
+        many_till(terminated(not_line_ending, line_ending), tag(label)),
+        preceded(space1, parser),
+    )
 }
 
 pub mod part1 {
     use super::*;
 
     pub fn run(input: &str) -> Result<String> {
-        let (_, races) = parse_races(input).unwrap();
+        Ok(solve_u128(input)?.to_string())
+    }
+
+    /// Multiplies the number of ways to win each race, in `u128` since the
+    /// product of many races can easily exceed `u64`.
+    ///
+    /// Errors if the product overflows even `u128`.
+    pub fn solve_u128(input: &str) -> Result<u128> {
+        let (_, races) = parse_races(input)
+            .map_err(|e| anyhow!("failed to parse races: {e}"))?;
 
-        Ok(races
-            .iter()
-            .map(|race| race.ways_to_win())
-            .product::<u64>()
-            .to_string())
+        races.iter().try_fold(1_u128, |acc, race| {
+            acc.checked_mul(race.ways_to_win() as u128)
+                .context("product of ways to win overflows u128")
+        })
+    }
+
+    /// Like `solve_u128`, but for races whose times and distances may be
+    /// decimals (e.g. `Time: 7.5`).
+    pub fn solve_decimal(input: &str) -> Result<u128> {
+        let (_, races) = parse_decimal_races(input)
+            .map_err(|e| anyhow!("failed to parse races: {e}"))?;
+
+        races.iter().try_fold(1_u128, |acc, race| {
+            acc.checked_mul(race.ways_to_win() as u128)
+                .context("product of ways to win overflows u128")
+        })
+    }
+
+    /// Parses list of races with decimal times and distances, which must be
+    /// finite (`double` would otherwise accept "inf" and "nan").
+    pub(super) fn parse_decimal_races(
+        input: &str,
+    ) -> IResult<&str, Vec<DecimalRace>> {
+        let finite = || verify(double, |n: &f64| n.is_finite());
+
+        let (_, times) =
+            labelled("Time:", separated_list1(space1, finite()))(input)?;
+
+        let (input, distances) =
+            labelled("Distance:", separated_list1(space1, finite()))(input)?;
+
+        Ok((
+            input,
+            times
+                .into_iter()
+                .zip(distances)
+                .map(|(time, dist)| DecimalRace { time, dist })
+                .collect(),
+        ))
     }
 
     /// Parses list of races.
     pub(super) fn parse_races(input: &str) -> IResult<&str, Vec<Race>> {
         // Parse list of race times
-        let (input, times) = preceded(
-            preceded(preceded(take_until1(":"), tag(":")), space1),
-            separated_list1(space1, u64),
-        )(input)?;
+        let (_, times) =
+            labelled("Time:", separated_list1(space1, u64))(input)?;
 
         // Parse list of race distances
-        let (input, distances) = preceded(
-            preceded(preceded(take_until1(":"), tag(":")), space1),
-            separated_list1(space1, u64),
-        )(input)?;
+        let (input, distances) =
+            labelled("Distance:", separated_list1(space1, u64))(input)?;
 
         Ok((
             input,
@@ -96,7 +395,8 @@ pub mod part2 {
     use super::*;
 
     pub fn run(input: &str) -> Result<String> {
-        let (_, race) = parse_race(input).unwrap();
+        let (_, race) = parse_race(input)
+            .map_err(|e| anyhow!("failed to parse race: {e}"))?;
 
         Ok(race.ways_to_win().to_string())
     }
@@ -104,18 +404,16 @@ pub mod part2 {
     /// Parses the single race.
     pub(super) fn parse_race(input: &str) -> IResult<&str, Race> {
         // Parse the merged race time
-        let (input, time) = preceded(
-            preceded(preceded(take_until1(":"), tag(":")), space1),
-            separated_list1(space1, digit1),
-        )(input)
+        let (_, time) = labelled("Time:", separated_list1(space1, digit1))(
+            input,
+        )
         .map(|(input, times)| (input, times.concat().parse().unwrap()))?;
 
         // Parse the merged race distance
-        let (input, dist) = preceded(
-            preceded(preceded(take_until1(":"), tag(":")), space1),
-            separated_list1(space1, digit1),
-        )(input)
-        .map(|(input, dists)| (input, dists.concat().parse().unwrap()))?;
+        let (input, dist) =
+            labelled("Distance:", separated_list1(space1, digit1))(input).map(
+                |(input, dists)| (input, dists.concat().parse().unwrap()),
+            )?;
 
         Ok((input, Race { time, dist }))
     }
@@ -133,6 +431,17 @@ Distance:  9  40  200";
         assert_eq!("288", part1::run(input).unwrap());
     }
 
+    #[test]
+    fn part1_solve_u128_ok() {
+        let input = "Time:      1000000 1000000 1000000 1000000
+Distance:  0       0       0       0";
+
+        let product = part1::solve_u128(input).unwrap();
+
+        assert!(product > u64::MAX as u128);
+        assert_eq!(999_999_u128.pow(4), product);
+    }
+
     #[test]
     fn part1_parse_races_ok() {
         let input = "Time:      7  15   30
@@ -149,6 +458,16 @@ Distance:  9  40  200";
         assert_eq!(races, part1::parse_races(input).unwrap().1);
     }
 
+    #[test]
+    fn parse_with_title_line_ok() {
+        let input = "Boat races: heat 1
+Time:      7  15   30
+Distance:  9  40  200";
+
+        assert_eq!("288", part1::run(input).unwrap());
+        assert_eq!("71503", part2::run(input).unwrap());
+    }
+
     #[test]
     fn part2_ok() {
         let input = "Time:      7  15   30
@@ -156,4 +475,151 @@ Distance:  9  40  200";
 
         assert_eq!("71503", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn race_solvers_agree() {
+        let small = Race {
+            time: 30,
+            dist: 200,
+        };
+        assert_eq!(Solver::Naive, small.solver());
+        assert_eq!(9, small.ways_to_win_naive());
+        assert_eq!(9, small.ways_to_win_fast());
+
+        let huge = Race {
+            time: 71530,
+            dist: 940200,
+        };
+        assert_eq!(Solver::Quadratic, huge.solver());
+        assert_eq!(71503, huge.ways_to_win_naive());
+        assert_eq!(71503, huge.ways_to_win_fast());
+    }
+
+    #[test]
+    fn race_is_winnable_ok() {
+        assert!(Race { time: 7, dist: 9 }.is_winnable());
+        assert!(Race { time: 7, dist: 11 }.is_winnable());
+        assert!(!Race { time: 7, dist: 12 }.is_winnable());
+        assert!(!Race { time: 0, dist: 0 }.is_winnable());
+        assert!(Race {
+            time: u64::MAX,
+            dist: u64::MAX
+        }
+        .is_winnable());
+    }
+
+    #[test]
+    fn part1_solve_decimal_ok() {
+        // Holding for 2, 3, 4 or 5 wins (6 * 1.5 only ties the record)
+        let input = "Time:      7.5
+Distance:  9";
+        assert_eq!(4, part1::solve_decimal(input).unwrap());
+
+        let input = "Time:      7.5  10.25  7
+Distance:  9    20.5   9";
+        assert_eq!(4 * 5 * 4, part1::solve_decimal(input).unwrap());
+
+        let input = "Time:      7  15   30
+Distance:  9  40  200";
+        assert_eq!(288, part1::solve_decimal(input).unwrap());
+
+        // A discriminant of 0: holding for half the time only ties the record
+        let input = "Time: 100000000000000000
+Distance: 2500000000000000000000000000000000
+";
+        assert_eq!(0, part1::solve_decimal(input).unwrap());
+    }
+
+    #[test]
+    fn part1_solve_decimal_err() {
+        assert!(part1::solve_decimal("Time: inf\nDistance: 9\n").is_err());
+        assert!(part1::solve_decimal("Time: 7\nDistance: nan\n").is_err());
+    }
+
+    #[test]
+    fn race_winning_bounds_ok() {
+        assert_eq!(Some((2, 5)), Race { time: 7, dist: 9 }.winning_bounds());
+        assert_eq!(Some((4, 11)), Race { time: 15, dist: 40 }.winning_bounds());
+        assert_eq!(
+            Some((11, 19)),
+            Race {
+                time: 30,
+                dist: 200
+            }
+            .winning_bounds()
+        );
+        assert_eq!(None, Race { time: 7, dist: 12 }.winning_bounds());
+        assert_eq!(None, Race { time: 6, dist: 9 }.winning_bounds());
+    }
+
+    #[test]
+    fn solve_tuple_ok() {
+        assert_eq!(4, solve((7, 9)).unwrap());
+    }
+
+    #[test]
+    fn solve_race_ok() {
+        assert_eq!(8, solve(Race { time: 15, dist: 40 }).unwrap());
+    }
+
+    #[test]
+    fn solve_races_ok() {
+        let races = vec![
+            Race { time: 7, dist: 9 },
+            Race { time: 15, dist: 40 },
+            Race {
+                time: 30,
+                dist: 200,
+            },
+        ];
+
+        assert_eq!(288, solve(races).unwrap());
+    }
+
+    #[test]
+    fn win_fractions_ok() {
+        let input = "Time:      7  15   30
+Distance:  9  40  200";
+
+        assert_eq!(vec![0.5, 0.5, 9.0 / 31.0], win_fractions(input).unwrap());
+    }
+
+    #[test]
+    fn kerned_vs_individual_ok() {
+        let input = "Time:      7  15   30
+Distance:  9  40  200";
+
+        assert_eq!((71503, 288), kerned_vs_individual(input).unwrap());
+    }
+
+    #[test]
+    fn tightest_race_ok() {
+        let input = "Time:      7  15   30
+Distance:  9  40  200";
+
+        assert_eq!(
+            (Race { time: 7, dist: 9 }, 4),
+            tightest_race(input).unwrap()
+        );
+
+        // Both races have 4 ways to win
+        let input = "Time:      9  7
+Distance:  17  9";
+
+        assert_eq!(
+            (Race { time: 7, dist: 9 }, 4),
+            tightest_race(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn ways_to_win_with_offset_ok() {
+        let race = Race { time: 7, dist: 9 };
+
+        // Holds 2..=5 win, but a head start of 3 leaves only 3..=5
+        assert_eq!(4, race.ways_to_win_with_offset(0));
+        assert_eq!(3, race.ways_to_win_with_offset(3));
+        assert!(race.ways_to_win_with_offset(3) < race.ways_to_win());
+        assert_eq!(0, race.ways_to_win_with_offset(6));
+    }
 }
@@ -1,349 +1,704 @@
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 
-use anyhow::Result;
-use nom::bytes::complete::take;
-use nom::character::complete::{space1, u32};
-use nom::sequence::separated_pair;
+use anyhow::{anyhow, bail, Context, Result};
+use nom::bytes::complete::{take, take_till1};
+use nom::character::complete::{char, space0, space1};
+use nom::combinator::{eof, opt, rest};
+use nom::sequence::{preceded, separated_pair, terminated, tuple};
 use nom::IResult;
 
-pub mod part1 {
-    use super::*;
+/// Possible hand type, from weakest (`HighCard`) to strongest (`FiveKind`).
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeKind,
+    FullHouse,
+    FourKind,
+    FiveKind,
+}
+
+impl HandType {
+    /// Deduces the hand type from the counts of each distinct non-wildcard
+    /// card in the hand, plus the number of wildcards.
+    fn from_counts(mut counts: Vec<usize>, wildcards: usize) -> Self {
+        // A wildcard morphs into whatever makes the strongest hand, meaning
+        // if we have a list of card counts, it will turn into the card with
+        // the biggest count because then it'd have improved the hand:
+        // [4] (`FourKind`) -> [5] (`FiveKind`, improved)
+        // [1, 3] (`ThreeKind`) -> [1, 4] (`FourKind`, improved)
+        // [2, 2] (`TwoPair`) -> [2, 3] (`FullHouse`, improved)
+        // [1, 1, 2] (`OnePair`) -> [1, 1, 3] (`ThreeKind`, improved)
+        //
+        // For that reason, first we sort the counts so we can take the last
+        // one (the biggest one)...
+        counts.sort();
+
+        // ...and bump it by how many wildcards are in the hand.
+        if let Some(last) = counts.last_mut() {
+            *last += wildcards;
+        } else {
+            // If the list of counts is empty, it must be that the hand is
+            // all wildcards (e.g. `JJJJJ`), so it's a `FiveKind`.
+            counts.push(5);
+        }
 
-    /// Possible cards, from weakest (`Two`) to strongest (`A`).
-    #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
-    enum Card {
-        Two,
-        Three,
-        Four,
-        Five,
-        Six,
-        Seven,
-        Eight,
-        Nine,
-        Ten,
-        J,
-        Q,
-        K,
-        A,
-    }
-
-    impl TryFrom<char> for Card {
-        type Error = String;
-
-        fn try_from(value: char) -> Result<Self, Self::Error> {
-            match value {
-                'A' => Ok(Self::A),
-                'K' => Ok(Self::K),
-                'Q' => Ok(Self::Q),
-                'J' => Ok(Self::J),
-                'T' => Ok(Self::Ten),
-                '9' => Ok(Self::Nine),
-                '8' => Ok(Self::Eight),
-                '7' => Ok(Self::Seven),
-                '6' => Ok(Self::Six),
-                '5' => Ok(Self::Five),
-                '4' => Ok(Self::Four),
-                '3' => Ok(Self::Three),
-                '2' => Ok(Self::Two),
-                _ => Err("cannot parse card from invalid char".to_string()),
+        // Deduce the hand type from the counts
+        match counts[..] {
+            [5] => Self::FiveKind,
+            [1, 4] | [4, 1] => Self::FourKind,
+            [2, 3] | [3, 2] => Self::FullHouse,
+            [1, 1, 3] | [1, 3, 1] | [3, 1, 1] => Self::ThreeKind,
+            [1, 2, 2] | [2, 1, 2] | [2, 2, 1] => Self::TwoPair,
+            [1, 1, 1, 2] | [1, 1, 2, 1] | [1, 2, 1, 1] | [2, 1, 1, 1] => {
+                Self::OnePair
             }
+            [1, 1, 1, 1, 1] => Self::HighCard,
+            _ => unreachable!(),
         }
     }
 
-    /// Possible hand type, from weakest (`HighCard`) to strongest (`FiveKind`).
-    #[derive(Eq, Ord, PartialEq, PartialOrd)]
-    enum HandType {
-        HighCard,
-        OnePair,
-        TwoPair,
-        ThreeKind,
-        FullHouse,
-        FourKind,
-        FiveKind,
-    }
-
-    impl From<&Vec<Card>> for HandType {
-        fn from(value: &Vec<Card>) -> Self {
-            // Count each distinct card type
-            let mut card_counts: HashMap<Card, usize> = HashMap::new();
-            for card in value {
-                card_counts
-                    .entry(*card)
-                    .and_modify(|count| {
-                        *count += 1;
-                    })
-                    .or_insert(1);
-            }
+    /// Deduces the hand type of the cards, with `wildcard` (if any) standing
+    /// in for whatever card makes the strongest hand.
+    fn of(cards: &[Card], wildcard: Option<Card>) -> Self {
+        let mut wildcards = 0;
+        let mut card_counts: HashMap<Card, usize> = HashMap::new();
 
-            // Deduce the hand type from the counts
-            match card_counts.values().collect::<Vec<_>>()[..] {
-                [5] => Self::FiveKind,
-                [1, 4] | [4, 1] => Self::FourKind,
-                [2, 3] | [3, 2] => Self::FullHouse,
-                [1, 1, 3] | [1, 3, 1] | [3, 1, 1] => Self::ThreeKind,
-                [1, 2, 2] | [2, 1, 2] | [2, 2, 1] => Self::TwoPair,
-                [1, 1, 1, 2] | [1, 1, 2, 1] | [1, 2, 1, 1] | [2, 1, 1, 1] => {
-                    Self::OnePair
-                }
-                [1, 1, 1, 1, 1] => Self::HighCard,
-                _ => unreachable!(),
+        // We count each distinct card type, EXCEPT for the wildcard. For that
+        // we keep a different counter (see `from_counts`).
+        for &card in cards {
+            if Some(card) == wildcard {
+                wildcards += 1;
+            } else {
+                *card_counts.entry(card).or_insert(0) += 1;
             }
         }
-    }
 
-    #[derive(Eq, PartialEq)]
-    struct Hand {
-        cards: Vec<Card>,
-        hand_type: HandType,
+        Self::from_counts(card_counts.into_values().collect(), wildcards)
     }
+}
 
-    impl PartialOrd for Hand {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            Some(self.cmp(other))
+/// Possible cards, from weakest (`Two`) to strongest (`A`).
+///
+/// When `J`s are jokers, they rank below `Two` instead when comparing hands.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Card {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    J,
+    Q,
+    K,
+    A,
+}
+
+impl Card {
+    /// How strong the card is when breaking ties between hands of the same
+    /// type, with the `wildcard` (if any) being the weakest.
+    fn strength(self, wildcard: Option<Card>) -> u8 {
+        if Some(self) == wildcard {
+            0
+        } else {
+            self as u8 + 1
         }
     }
+}
 
-    impl Ord for Hand {
-        fn cmp(&self, other: &Self) -> Ordering {
-            match self.hand_type.cmp(&other.hand_type) {
-                Ordering::Less => Ordering::Less,
-                Ordering::Greater => Ordering::Greater,
-                // If two hands have equal hand types, then check each card
-                // for ordering.
-                Ordering::Equal => self
-                    .cards
-                    .iter()
-                    .zip(&other.cards)
-                    .find_map(|(mine, other)| {
-                        (!mine.cmp(other).is_eq()).then_some(mine.cmp(other))
-                    })
-                    .unwrap(),
-            }
+impl TryFrom<char> for Card {
+    type Error = String;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value.to_ascii_uppercase() {
+            'A' => Ok(Self::A),
+            'K' => Ok(Self::K),
+            'Q' => Ok(Self::Q),
+            'J' => Ok(Self::J),
+            'T' => Ok(Self::Ten),
+            '9' => Ok(Self::Nine),
+            '8' => Ok(Self::Eight),
+            '7' => Ok(Self::Seven),
+            '6' => Ok(Self::Six),
+            '5' => Ok(Self::Five),
+            '4' => Ok(Self::Four),
+            '3' => Ok(Self::Three),
+            '2' => Ok(Self::Two),
+            _ => Err("cannot parse card from invalid char".to_string()),
         }
     }
+}
 
-    impl FromStr for Hand {
-        type Err = String;
+/// A hand of five cards, ordered by type and then card by card.
+///
+/// With `JOKERS` set (part 2), `J`s stand in for whatever card makes the
+/// strongest hand type, but are the weakest cards when breaking ties.
+/// [`part1::Hand`] and [`part2::Hand`] are the two flavours.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Hand<const JOKERS: bool> {
+    cards: Vec<Card>,
+    hand_type: HandType,
+}
 
-        fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let cards = s
-                .chars()
-                .map(|char| char.try_into())
-                .collect::<Result<Vec<Card>, _>>()?;
+impl<const JOKERS: bool> Hand<JOKERS> {
+    /// The card acting as a wildcard under these rules, if any.
+    const WILDCARD: Option<Card> = if JOKERS { Some(Card::J) } else { None };
 
-            let hand_type: HandType = (&cards).into();
+    /// Makes a hand out of the given cards, working out its type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't exactly five cards.
+    pub fn from_cards(cards: Vec<Card>) -> Self {
+        assert_eq!(5, cards.len(), "a hand must have exactly 5 cards");
+        let hand_type = HandType::of(&cards, Self::WILDCARD);
 
-            Ok(Self { cards, hand_type })
-        }
+        Self { cards, hand_type }
     }
 
-    pub fn run(input: &str) -> Result<String> {
-        // We use a BTreeMap here because it produces items in key order, so
-        // we auto get weakest to strongest `Hand`s when iterating through it.
-        let hands = input
-            .lines()
-            .map(|input| parse_hand_bid(input).unwrap().1)
-            .collect::<BTreeMap<Hand, u32>>();
-
-        let total = hands
-            .iter()
-            .enumerate()
-            .map(|(i, (_, bid))| (i as u32 + 1) * bid)
-            .sum::<u32>();
+    /// The type of the hand, e.g.:
+    /// ```
+    /// use day07::{part1, part2, HandType};
+    ///
+    /// let hand: part1::Hand = "KTJJT".parse().unwrap();
+    /// assert_eq!(HandType::TwoPair, hand.hand_type());
+    ///
+    /// // With the `J`s as jokers, they join the `T`s
+    /// let hand: part2::Hand = "KTJJT".parse().unwrap();
+    /// assert_eq!(HandType::FourKind, hand.hand_type());
+    /// ```
+    pub fn hand_type(&self) -> HandType {
+        self.hand_type
+    }
 
-        Ok(total.to_string())
+    /// The strength of each card in order, with `wildcard` (if any) being the
+    /// weakest.
+    fn strengths(&self, wildcard: Option<Card>) -> Vec<u8> {
+        self.cards
+            .iter()
+            .map(|card| card.strength(wildcard))
+            .collect()
     }
 
-    /// Parses the hand and bid from the input.
-    fn parse_hand_bid(input: &str) -> IResult<&str, (Hand, u32)> {
-        separated_pair(take(5usize), space1, u32)(input)
-            .map(|(input, (hand, bid))| (input, (hand.parse().unwrap(), bid)))
+    /// What the hand would be ranked by if `wildcard` (rather than the card
+    /// these rules pick) were the wildcard: its type, then its card strengths.
+    fn rank_key(&self, wildcard: Option<Card>) -> (HandType, Vec<u8>) {
+        (
+            HandType::of(&self.cards, wildcard),
+            self.strengths(wildcard),
+        )
     }
 }
 
-pub mod part2 {
-    use super::*;
+/// What the shared helpers below need from a hand, under either part's rules.
+trait RankedHand: Ord + FromStr<Err = String> {
+    fn hand_type(&self) -> HandType;
+}
 
-    /// Possible cards, from weakest (`J`) to strongest (`A`).
-    #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
-    enum Card {
-        J,
-        Two,
-        Three,
-        Four,
-        Five,
-        Six,
-        Seven,
-        Eight,
-        Nine,
-        Ten,
-        Q,
-        K,
-        A,
-    }
-
-    impl TryFrom<char> for Card {
-        type Error = String;
-
-        fn try_from(value: char) -> Result<Self, Self::Error> {
-            match value {
-                'A' => Ok(Self::A),
-                'K' => Ok(Self::K),
-                'Q' => Ok(Self::Q),
-                'J' => Ok(Self::J),
-                'T' => Ok(Self::Ten),
-                '9' => Ok(Self::Nine),
-                '8' => Ok(Self::Eight),
-                '7' => Ok(Self::Seven),
-                '6' => Ok(Self::Six),
-                '5' => Ok(Self::Five),
-                '4' => Ok(Self::Four),
-                '3' => Ok(Self::Three),
-                '2' => Ok(Self::Two),
-                _ => Err("cannot parse card from invalid char".to_string()),
-            }
+impl<const JOKERS: bool> RankedHand for Hand<JOKERS> {
+    fn hand_type(&self) -> HandType {
+        self.hand_type
+    }
+}
+
+/// Calls the generic function `$f` with the hand type of the part 2 rules if
+/// `$jokers` is set, or of the part 1 rules otherwise.
+macro_rules! with_rules {
+    ($jokers:expr, $f:ident($($arg:expr),*)) => {
+        if $jokers {
+            $f::<part2::Hand>($($arg),*)
+        } else {
+            $f::<part1::Hand>($($arg),*)
         }
+    };
+}
+
+impl<const JOKERS: bool> PartialOrd for Hand<JOKERS> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    /// Possible hand type, from weakest (`HighCard`) to strongest (`FiveKind`).
-    #[derive(Eq, Ord, PartialEq, PartialOrd)]
-    enum HandType {
-        HighCard,
-        OnePair,
-        TwoPair,
-        ThreeKind,
-        FullHouse,
-        FourKind,
-        FiveKind,
-    }
-
-    impl From<&Vec<Card>> for HandType {
-        fn from(value: &Vec<Card>) -> Self {
-            let mut wildcards = 0;
-            let mut card_counts: HashMap<Card, usize> = HashMap::new();
-
-            // We count each distinct card type, EXCEPT for `J`s (the wildcard).
-            // For that we keep a different counter. Reason below.
-            for card in value {
-                if matches!(card, Card::J) {
-                    wildcards += 1;
-                } else {
-                    card_counts
-                        .entry(*card)
-                        .and_modify(|count| {
-                            *count += 1;
-                        })
-                        .or_insert(1);
-                }
-            }
+impl<const JOKERS: bool> Ord for Hand<JOKERS> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // If two hands have equal hand types, then check each card for
+        // ordering. Every card being the same means the hands are identical.
+        self.hand_type.cmp(&other.hand_type).then_with(|| {
+            self.strengths(Self::WILDCARD)
+                .cmp(&other.strengths(Self::WILDCARD))
+        })
+    }
+}
 
-            // `J` morphs into whatever makes the strongest hand, meaning if we
-            // have a list of card counts, it will turn into the card with the
-            // biggest count because then it'd have improved the hand:
-            // [4] (`FourKind`) -> [5] (`FiveKind`, improved)
-            // [1, 3] (`ThreeKind`) -> [1, 4] (`FourKind`, improved)
-            // [2, 2] (`TwoPair`) -> [2, 3] (`FullHouse`, improved)
-            // [1, 1, 2] (`OnePair`) -> [1, 1, 3] (`ThreeKind`, improved)
-            //
-            // For that reason, first we sort the counts so we can take the last
-            // one (the biggest one)...
-            let mut counts = card_counts.into_values().collect::<Vec<_>>();
-            counts.sort();
-
-            // ...and bump it by how many wildcards are in the hand.
-            if let Some(last) = counts.last_mut() {
-                *last += wildcards;
-            } else {
-                // If the list of counts is empty, it must be that the hand is
-                // all wildcards (`JJJJJ`), so it's a `FiveKind`.
-                counts.push(5);
-            }
+impl<const JOKERS: bool> FromStr for Hand<JOKERS> {
+    type Err = String;
 
-            // Deduce the hand type from the counts
-            match counts[..] {
-                [5] => Self::FiveKind,
-                [1, 4] | [4, 1] => Self::FourKind,
-                [2, 3] | [3, 2] => Self::FullHouse,
-                [1, 1, 3] | [1, 3, 1] | [3, 1, 1] => Self::ThreeKind,
-                [1, 2, 2] | [2, 1, 2] | [2, 2, 1] => Self::TwoPair,
-                [1, 1, 1, 2] | [1, 1, 2, 1] | [1, 2, 1, 1] | [2, 1, 1, 1] => {
-                    Self::OnePair
-                }
-                [1, 1, 1, 1, 1] => Self::HighCard,
-                _ => unreachable!(),
-            }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cards = s
+            .chars()
+            .map(|char| char.try_into())
+            .collect::<Result<Vec<Card>, _>>()?;
+        if cards.len() != 5 {
+            return Err("a hand must have exactly 5 cards".to_string());
         }
+
+        Ok(Self::from_cards(cards))
     }
+}
+
+/// Adds up every hand's bid multiplied by its rank, the weakest hand having
+/// rank 1.
+fn total_winnings<H>(input: &str) -> Result<u64>
+where
+    H: RankedHand,
+{
+    sum_winnings(ranked_bids::<H>(input)?)
+}
+
+/// Adds up each bid multiplied by its rank, given the bids from the weakest
+/// hand (rank 1) to the strongest.
+///
+/// Errors if the total overflows `u64`.
+fn sum_winnings(bids: impl IntoIterator<Item = u64>) -> Result<u64> {
+    bids.into_iter()
+        .zip(1..)
+        .try_fold(0_u64, |total, (bid, rank)| {
+            winning(rank, bid)
+                .and_then(|winning| total.checked_add(winning))
+                .context("total winnings overflow u64")
+        })
+}
+
+/// How much a hand at `rank` wins with its `bid`, None if it overflows `u64`.
+fn winning(rank: u64, bid: u64) -> Option<u64> {
+    rank.checked_mul(bid)
+}
+
+/// Lists every hand (as written) with its bid and rank, from the weakest hand
+/// (rank 1) to the strongest. Every line is kept, even if the same hand shows
+/// up more than once.
+fn standings_of<H>(input: &str) -> Result<Vec<(String, u64, u32)>>
+where
+    H: RankedHand,
+{
+    ranked_texts::<H>(input)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, (hand, bid))| {
+            let rank =
+                u32::try_from(i + 1).context("too many hands to rank")?;
+
+            Ok((hand.to_string(), bid, rank))
+        })
+        .collect()
+}
+
+/// Returns how much the total winnings change by if `added_hand` (with bid
+/// `added_bid`) were added as the last line of the input.
+///
+/// The added hand takes the rank right above every hand not stronger than it,
+/// and every stronger hand moves up a rank (winning its bid once more), so the
+/// change can be worked out without recomputing the whole total.
+pub fn winnings_delta(
+    input: &str,
+    added_hand: &str,
+    added_bid: u64,
+    jokers: bool,
+) -> Result<i64> {
+    with_rules!(jokers, delta(input, added_hand, added_bid))
+}
+
+fn delta<H>(input: &str, added_hand: &str, added_bid: u64) -> Result<i64>
+where
+    H: RankedHand,
+{
+    let hands = ranked::<H>(input)?;
+    let added: H = added_hand.parse().map_err(|e: String| anyhow!(e))?;
+
+    // Equal hands keep their input order, so the added (last) hand ends up
+    // after all the hands equal to it.
+    let position = hands.partition_point(|(hand, _, _)| *hand <= added);
+    let rank = position as u64 + 1;
+    let shifted = hands[position..]
+        .iter()
+        .try_fold(0_u64, |total, (_, _, bid)| total.checked_add(*bid));
+
+    let delta = winning(rank, added_bid)
+        .zip(shifted)
+        .and_then(|(winning, shifted)| winning.checked_add(shifted))
+        .context("winnings delta overflows u64")?;
+
+    Ok(i64::try_from(delta)?)
+}
+
+/// Returns the expected total winnings if hands that tie were ranked in a
+/// uniformly random order among themselves.
+///
+/// A group of `k` tied hands occupies `k` consecutive ranks, so each of them
+/// is expected to get the average of those ranks.
+pub fn expected_winnings(input: &str, jokers: bool) -> Result<f64> {
+    with_rules!(jokers, expected(input))
+}
 
-    #[derive(Eq, PartialEq)]
-    struct Hand {
-        cards: Vec<Card>,
-        hand_type: HandType,
+fn expected<H>(input: &str) -> Result<f64>
+where
+    H: RankedHand,
+{
+    let hands = ranked::<H>(input)?;
+
+    let mut total = 0.0;
+    let mut first_rank = 1;
+    for group in hands.chunk_by(|(a, _, _), (b, _, _)| a == b) {
+        let last_rank = first_rank + group.len() - 1;
+        let mean_rank = (first_rank + last_rank) as f64 / 2.0;
+        let bids = group.iter().map(|(_, _, bid)| bid).sum::<u64>();
+
+        total += bids as f64 * mean_rank;
+        first_rank = last_rank + 1;
     }
 
-    impl PartialOrd for Hand {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            Some(self.cmp(other))
-        }
+    Ok(total)
+}
+
+/// Returns the bid of the hand at the given rank, where the weakest hand has
+/// rank 1.
+pub fn bid_at_rank(input: &str, rank: usize, jokers: bool) -> Result<u64> {
+    let bids = with_rules!(jokers, ranked_bids(input))?;
+
+    rank.checked_sub(1)
+        .and_then(|i| bids.get(i))
+        .copied()
+        .with_context(|| {
+            format!("rank {} is out of range 1..={}", rank, bids.len())
+        })
+}
+
+/// Returns the total winnings as if every bid were 1, i.e. the sum of all the
+/// ranks. Comparing it against the real winnings shows how much the bids
+/// (rather than the ranking) make up the total.
+pub fn rank_sum(input: &str, jokers: bool) -> Result<u64> {
+    let hands = with_rules!(jokers, ranked_bids(input))?.len() as u64;
+
+    Ok(hands * (hands + 1) / 2)
+}
+
+/// Groups the hands (as written) by their type, each group from the weakest
+/// hand to the strongest.
+pub fn group_by_type(
+    input: &str,
+    jokers: bool,
+) -> Result<BTreeMap<HandType, Vec<String>>> {
+    with_rules!(jokers, group_by_type_of(input))
+}
+
+fn group_by_type_of<H: RankedHand>(
+    input: &str,
+) -> Result<BTreeMap<HandType, Vec<String>>> {
+    let mut groups: BTreeMap<HandType, Vec<String>> = BTreeMap::new();
+    for (hand, text, _) in ranked::<H>(input)? {
+        groups
+            .entry(hand.hand_type())
+            .or_default()
+            .push(text.to_string());
+    }
+
+    Ok(groups)
+}
+
+/// Splits the total winnings by hand type: how much the hands of each type
+/// (with their rank and bid) add to it.
+pub fn winnings_by_type(
+    input: &str,
+    jokers: bool,
+) -> Result<BTreeMap<HandType, u64>> {
+    with_rules!(jokers, winnings_by_type_of(input))
+}
+
+fn winnings_by_type_of<H: RankedHand>(
+    input: &str,
+) -> Result<BTreeMap<HandType, u64>> {
+    let mut winnings = BTreeMap::new();
+    for ((hand, _, bid), rank) in ranked::<H>(input)?.iter().zip(1..) {
+        let total: &mut u64 = winnings.entry(hand.hand_type()).or_insert(0);
+        *total = winning(rank, *bid)
+            .and_then(|winning| total.checked_add(winning))
+            .context("winnings overflow u64")?;
     }
 
-    impl Ord for Hand {
-        fn cmp(&self, other: &Self) -> Ordering {
-            match self.hand_type.cmp(&other.hand_type) {
-                Ordering::Less => Ordering::Less,
-                Ordering::Greater => Ordering::Greater,
-                // If two hands have equal hand types, then check each card
-                // for ordering.
-                Ordering::Equal => self
-                    .cards
-                    .iter()
-                    .zip(&other.cards)
-                    .find_map(|(mine, other)| {
-                        (!mine.cmp(other).is_eq()).then_some(mine.cmp(other))
-                    })
-                    .unwrap(),
+    Ok(winnings)
+}
+
+/// Returns the most common hand type, the stronger type winning ties.
+pub fn modal_type(input: &str, jokers: bool) -> Result<HandType> {
+    // Groups come in order of strength, and `max_by_key` picks the last of the
+    // equally big ones
+    group_by_type(input, jokers)?
+        .into_iter()
+        .max_by_key(|(_, hands)| hands.len())
+        .map(|(hand_type, _)| hand_type)
+        .context("no hands in input")
+}
+
+/// Hands (as written) along with how much their rank changed.
+pub type RankChanges = Vec<(String, i64)>;
+
+/// Compares the rankings of two inputs, returning:
+/// - each hand found in both along with how much its rank changed (its rank
+///   in `b` minus its rank in `a`), in `a`'s ranking order,
+/// - the hands found only in `a`, in `a`'s ranking order,
+/// - the hands found only in `b`, in `b`'s ranking order.
+///
+/// Hands are matched by their cards (case aside). If a hand appears more than
+/// once, its occurrences are matched up in order of rank.
+pub fn ranking_diff(
+    a: &str,
+    b: &str,
+    jokers: bool,
+) -> Result<(RankChanges, Vec<String>, Vec<String>)> {
+    let a = with_rules!(jokers, ranked_texts(a))?;
+    let b = with_rules!(jokers, ranked_texts(b))?;
+
+    // Positions in `b` of each hand, weakest first, popped off as they're
+    // matched
+    let mut b_positions: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, (hand, _)) in b.iter().enumerate().rev() {
+        b_positions
+            .entry(hand.to_ascii_uppercase())
+            .or_default()
+            .push(i);
+    }
+
+    let mut changed = Vec::new();
+    let mut only_in_a = Vec::new();
+    let mut matched_in_b = vec![false; b.len()];
+    for (i, (hand, _)) in a.iter().enumerate() {
+        let b_position = b_positions
+            .get_mut(&hand.to_ascii_uppercase())
+            .and_then(|positions| positions.pop());
+
+        match b_position {
+            Some(j) => {
+                matched_in_b[j] = true;
+                changed.push((hand.to_string(), j as i64 - i as i64));
             }
+            None => only_in_a.push(hand.to_string()),
+        }
+    }
+
+    let only_in_b = b
+        .iter()
+        .zip(matched_in_b)
+        .filter(|(_, matched)| !matched)
+        .map(|((hand, _), _)| hand.to_string())
+        .collect();
+
+    Ok((changed, only_in_a, only_in_b))
+}
+
+/// Lists every hand's bid, from the weakest hand to the strongest.
+fn ranked_bids<H>(input: &str) -> Result<Vec<u64>>
+where
+    H: RankedHand,
+{
+    Ok(ranked::<H>(input)?
+        .into_iter()
+        .map(|(_, _, bid)| bid)
+        .collect())
+}
+
+/// Returns the weakest and the strongest hands in the input, as written.
+pub fn extremes(input: &str, jokers: bool) -> Result<(String, String)> {
+    with_rules!(jokers, extremes_of(input))
+}
+
+fn extremes_of<H>(input: &str) -> Result<(String, String)>
+where
+    H: RankedHand,
+{
+    let hands = ranked_texts::<H>(input)?;
+
+    let (weakest, _) = hands.first().context("no hands in input")?;
+    let (strongest, _) = hands.last().context("no hands in input")?;
+
+    Ok((weakest.to_string(), strongest.to_string()))
+}
+
+/// Returns a hash of the ranked hands and their bids, from the weakest hand to
+/// the strongest. Inputs ranking the same hands (case aside) with the same bids
+/// in the same order hash the same, whatever order their lines are in.
+///
+/// The hash (64-bit FNV-1a) is fixed, so fingerprints stay the same across
+/// runs and can be cached.
+pub fn ranking_fingerprint(input: &str, jokers: bool) -> Result<u64> {
+    let hands = with_rules!(jokers, ranked_texts(input))?;
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
         }
+    };
+
+    for (hand, bid) in hands {
+        feed(hand.to_ascii_uppercase().as_bytes());
+        feed(&bid.to_le_bytes());
     }
 
-    impl FromStr for Hand {
-        type Err = String;
+    Ok(hash)
+}
 
-        fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let cards = s
-                .chars()
-                .map(|char| char.try_into())
-                .collect::<Result<Vec<Card>, _>>()?;
+/// Like `ranked`, but listing the hands as written in the input.
+fn ranked_texts<H>(input: &str) -> Result<Vec<(&str, u64)>>
+where
+    H: RankedHand,
+{
+    Ok(ranked::<H>(input)?
+        .into_iter()
+        .map(|(_, text, bid)| (text, bid))
+        .collect())
+}
 
-            let hand_type: HandType = (&cards).into();
+/// Checks that no hand has the same card more than once.
+pub fn validate_distinct(input: &str) -> Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        // Make sure the line is a valid hand and bid to begin with
+        let (_, hand, _) = parse_hand_bid::<part1::Hand>(line)
+            .with_context(|| format!("line {}", i + 1))?;
 
-            Ok(Self { cards, hand_type })
+        let mut seen = HashSet::new();
+        for card in hand.chars().map(|card| card.to_ascii_uppercase()) {
+            if !seen.insert(card) {
+                bail!(
+                    "line {}: hand {:?} repeats card {:?}",
+                    i + 1,
+                    hand,
+                    card
+                );
+            }
         }
     }
 
+    Ok(())
+}
+
+/// Parses every line's hand (along with the hand as written) and bid, sorted
+/// from weakest to strongest hand.
+fn ranked<H>(input: &str) -> Result<Vec<(H, &str, u64)>>
+where
+    H: RankedHand,
+{
+    let mut hands = parse_lines::<H>(input)?;
+
+    // A stable sort, so equal hands stay in input order
+    hands.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+    Ok(hands)
+}
+
+/// Parses every line's hand (along with the hand as written) and bid, in
+/// input order.
+fn parse_lines<H: FromStr<Err = String>>(
+    input: &str,
+) -> Result<Vec<(H, &str, u64)>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            parse_hand_bid(line).with_context(|| format!("line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Parses the hand and bid from the input, also returning the hand as
+/// written.
+fn parse_hand_bid<H: FromStr<Err = String>>(
+    input: &str,
+) -> Result<(H, &str, u64)> {
+    let (_, (text, bid)) = split_hand_bid(input)
+        .map_err(|e| anyhow!("cannot split hand and bid: {e}"))?;
+
+    let hand = text.parse().map_err(|e: String| anyhow!(e))?;
+    let bid = bid
+        .parse()
+        .with_context(|| format!("invalid bid {:?}", bid))?;
+
+    Ok((hand, text, bid))
+}
+
+/// Splits a line like `"32T3K 765"` into its hand and bid tokens.
+///
+/// The line may end with a `#`-prefixed comment, which is ignored, e.g.
+/// `"32T3K 765 # strong"`.
+fn split_hand_bid(input: &str) -> IResult<&str, (&str, &str)> {
+    terminated(
+        separated_pair(
+            take(5usize),
+            space1,
+            take_till1(|c: char| c.is_whitespace()),
+        ),
+        tuple((space0, opt(preceded(char('#'), rest)), eof)),
+    )(input)
+}
+
+pub mod part1 {
+    use super::*;
+
+    pub use super::Card;
+
+    /// A hand under the normal rules, where `J`s are jacks.
+    pub type Hand = super::Hand<false>;
+
     pub fn run(input: &str) -> Result<String> {
-        // We use a BTreeMap here because it produces items in key order, so
-        // we auto get weakest to strongest `Hand`s when iterating through it.
-        let hands = input
-            .lines()
-            .map(|input| parse_hand_bid(input).unwrap().1)
-            .collect::<BTreeMap<Hand, u32>>();
-
-        let total = hands
-            .iter()
-            .enumerate()
-            .map(|(i, (_, bid))| (i as u32 + 1) * bid)
-            .sum::<u32>();
+        Ok(total_winnings::<Hand>(input)?.to_string())
+    }
+
+    /// Lists every hand (as written) with its bid and final rank, from the
+    /// weakest hand (rank 1) to the strongest. The total winnings are the sum
+    /// of each rank times its bid.
+    pub fn standings(input: &str) -> Result<Vec<(String, u64, u32)>> {
+        standings_of::<Hand>(input)
+    }
+}
+
+pub mod part2 {
+    use super::*;
+
+    pub use super::Card;
+
+    /// A hand under the part 2 rules, where `J`s are jokers.
+    pub type Hand = super::Hand<true>;
 
-        Ok(total.to_string())
+    /// Solves for the total winnings with `wildcard` (if any) as the card that
+    /// stands in for whatever makes the strongest hand, while being the
+    /// weakest card when breaking ties. `Some('J')` gives the part 2 answer,
+    /// and `None` the part 1 answer.
+    pub fn solve(input: &str, wildcard: Option<char>) -> Result<u64> {
+        let wildcard = wildcard
+            .map(|card| {
+                Card::try_from(card)
+                    .map_err(|_| anyhow!("invalid wildcard card {:?}", card))
+            })
+            .transpose()?;
+
+        // The hands are parsed under the part 1 rules (no wildcard), then
+        // ranked as if `wildcard` were the wildcard. `sort_by_cached_key` is
+        // stable, so equal hands stay in input order.
+        let mut hands = parse_lines::<super::part1::Hand>(input)?;
+        hands.sort_by_cached_key(|(hand, _, _)| hand.rank_key(wildcard));
+
+        sum_winnings(hands.into_iter().map(|(_, _, bid)| bid))
     }
 
-    /// Parses the hand and bid from the input.
-    fn parse_hand_bid(input: &str) -> IResult<&str, (Hand, u32)> {
-        separated_pair(take(5usize), space1, u32)(input)
-            .map(|(input, (hand, bid))| (input, (hand.parse().unwrap(), bid)))
+    pub fn run(input: &str) -> Result<String> {
+        Ok(total_winnings::<Hand>(input)?.to_string())
     }
 }
 
@@ -372,4 +727,385 @@ QQQJA 483";
 
         assert_eq!("5905", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn part1_invalid_bid_err() {
+        let input = "32T3K 765
+T55J5 99999999999999999999999";
+
+        let err = part1::run(input).unwrap_err();
+        assert_eq!(
+            "line 2: invalid bid \"99999999999999999999999\": number too large to fit in target type",
+            format!("{:#}", err)
+        );
+    }
+
+    #[test]
+    fn winnings_overflow_err() {
+        let input = "AAAAA 18446744073709551615
+22345 1";
+
+        assert!(part1::run(input).is_err());
+        assert!(part2::run(input).is_err());
+        assert!(part2::solve(input, Some('2')).is_err());
+        assert!(winnings_by_type(input, false).is_err());
+    }
+
+    #[test]
+    fn identical_hands_cmp_equal() {
+        let a: part1::Hand = "KK677".parse().unwrap();
+        let b: part1::Hand = "KK677".parse().unwrap();
+        assert_eq!(Ordering::Equal, a.cmp(&b));
+
+        let a: part2::Hand = "KTJJT".parse().unwrap();
+        let b: part2::Hand = "KTJJT".parse().unwrap();
+        assert_eq!(Ordering::Equal, a.cmp(&b));
+    }
+
+    #[test]
+    fn commented_hand_bid_ok() {
+        assert_eq!(
+            ("32T3K", "765"),
+            split_hand_bid("32T3K 765 # strong").unwrap().1
+        );
+
+        let input = "32T3K 765 # weakest
+T55J5 684
+KK677 28 #
+KTJJT 220
+QQQJA 483 # strongest";
+
+        assert_eq!("6440", part1::run(input).unwrap());
+    }
+
+    #[test]
+    fn winnings_delta_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        for jokers in [false, true] {
+            let run = if jokers { part2::run } else { part1::run };
+            let before = run(input).unwrap().parse::<i64>().unwrap();
+            let after = run(&format!("{input}\nAAAAA 100"))
+                .unwrap()
+                .parse::<i64>()
+                .unwrap();
+
+            assert_eq!(
+                after - before,
+                winnings_delta(input, "AAAAA", 100, jokers).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn expected_winnings_ok() {
+        let input = "32T3K 765
+KK677 28
+32T3K 100";
+
+        // The two 32T3K hands share ranks 1 and 2, so each expects rank 1.5
+        let expected = (765.0 + 100.0) * 1.5 + 28.0 * 3.0;
+        assert_eq!(expected, expected_winnings(input, false).unwrap());
+        assert_eq!(expected, expected_winnings(input, true).unwrap());
+
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+        assert_eq!(6440.0, expected_winnings(input, false).unwrap());
+    }
+
+    #[test]
+    fn lowercase_cards_ok() {
+        let (lower, _, _) = parse_hand_bid::<part1::Hand>("qqqja 483").unwrap();
+        let (upper, _, _) = parse_hand_bid::<part1::Hand>("QQQJA 483").unwrap();
+        assert_eq!(Ordering::Equal, lower.cmp(&upper));
+
+        let (lower, _, _) = parse_hand_bid::<part2::Hand>("qqqja 483").unwrap();
+        let (upper, _, _) = parse_hand_bid::<part2::Hand>("QQQJA 483").unwrap();
+        assert_eq!(Ordering::Equal, lower.cmp(&upper));
+
+        let input = "32t3k 765
+T55j5 684
+kk677 28
+KtJJT 220
+qqqja 483";
+
+        assert_eq!("6440", part1::run(input).unwrap());
+        assert_eq!("5905", part2::run(input).unwrap());
+    }
+
+    #[test]
+    fn extremes_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        assert_eq!(
+            ("32T3K".to_string(), "QQQJA".to_string()),
+            extremes(input, false).unwrap()
+        );
+        assert_eq!(
+            ("32T3K".to_string(), "KTJJT".to_string()),
+            extremes(input, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn bid_at_rank_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        assert_eq!(765, bid_at_rank(input, 1, false).unwrap());
+        assert_eq!(483, bid_at_rank(input, 5, false).unwrap());
+        assert_eq!(220, bid_at_rank(input, 5, true).unwrap());
+        assert!(bid_at_rank(input, 0, false).is_err());
+        assert!(bid_at_rank(input, 6, false).is_err());
+    }
+
+    #[test]
+    fn part2_solve_wildcard_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        assert_eq!(5905, part2::solve(input, Some('J')).unwrap());
+        assert_eq!(6440, part2::solve(input, None).unwrap());
+        assert_eq!(7722, part2::solve(input, Some('2')).unwrap());
+        assert!(part2::solve(input, Some('X')).is_err());
+    }
+
+    #[test]
+    fn ranking_fingerprint_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+        let shuffled = "qqqja 483
+KK677 28
+32T3K 765
+KTJJT 220
+T55J5 684";
+        let swapped_bids = "32T3K 684
+T55J5 765
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        let fingerprint = ranking_fingerprint(input, false).unwrap();
+        assert_eq!(fingerprint, ranking_fingerprint(shuffled, false).unwrap());
+        assert_ne!(
+            fingerprint,
+            ranking_fingerprint(swapped_bids, false).unwrap()
+        );
+        assert_ne!(fingerprint, ranking_fingerprint(input, true).unwrap());
+    }
+
+    #[test]
+    fn validate_distinct_ok() {
+        assert!(validate_distinct("23456 10\nAKQJT 20").is_ok());
+
+        let err = validate_distinct("23456 10\n33332 20").unwrap_err();
+        assert_eq!("line 2: hand \"33332\" repeats card '3'", err.to_string());
+    }
+
+    #[test]
+    fn rank_sum_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        assert_eq!(15, rank_sum(input, false).unwrap());
+        assert_eq!(15, rank_sum(input, true).unwrap());
+        assert_eq!(0, rank_sum("", false).unwrap());
+    }
+
+    #[test]
+    fn group_by_type_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        let groups = BTreeMap::from([
+            (HandType::OnePair, vec!["32T3K".to_string()]),
+            (
+                HandType::TwoPair,
+                vec!["KTJJT".to_string(), "KK677".to_string()],
+            ),
+            (
+                HandType::ThreeKind,
+                vec!["T55J5".to_string(), "QQQJA".to_string()],
+            ),
+        ]);
+
+        assert_eq!(groups, group_by_type(input, false).unwrap());
+    }
+
+    #[test]
+    fn modal_type_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        assert_eq!(HandType::ThreeKind, modal_type(input, false).unwrap());
+        assert_eq!(HandType::FourKind, modal_type(input, true).unwrap());
+        assert!(modal_type("", false).is_err());
+    }
+
+    #[test]
+    fn ranking_diff_ok() {
+        let a = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+        let b = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+KK688 100";
+
+        let (changed, only_in_a, only_in_b) =
+            ranking_diff(a, b, false).unwrap();
+        assert_eq!(
+            vec![
+                ("32T3K".to_string(), 0),
+                ("KTJJT".to_string(), 0),
+                ("KK677".to_string(), 0),
+                ("T55J5".to_string(), 1),
+                ("QQQJA".to_string(), 1),
+            ],
+            changed
+        );
+        assert!(only_in_a.is_empty());
+        assert_eq!(vec!["KK688".to_string()], only_in_b);
+
+        // The other way round, the added hand is only in `a`
+        let (changed, only_in_a, only_in_b) =
+            ranking_diff(b, a, false).unwrap();
+        assert_eq!(
+            vec![0, 0, 0, -1, -1],
+            changed
+                .into_iter()
+                .map(|(_, change)| change)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["KK688".to_string()], only_in_a);
+        assert!(only_in_b.is_empty());
+    }
+
+    #[test]
+    fn duplicate_hands_ok() {
+        let input = "AAAAA 1
+23456 3
+AAAAA 2";
+
+        // 23456 is rank 1, and the two AAAAA take ranks 2 and 3 in input order
+        assert_eq!("11", part1::run(input).unwrap());
+        assert_eq!("11", part2::run(input).unwrap());
+    }
+
+    #[test]
+    fn hand_from_cards_ok() {
+        use part1::{Card, Hand};
+
+        let hand = Hand::from_cards(vec![
+            Card::K,
+            Card::Ten,
+            Card::J,
+            Card::J,
+            Card::Ten,
+        ]);
+
+        assert_eq!(HandType::TwoPair, hand.hand_type());
+        assert_eq!(Ok(hand), "KTJJT".parse());
+        assert!("KTJJ".parse::<Hand>().is_err());
+    }
+
+    #[test]
+    fn part1_standings_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        let standings = part1::standings(input).unwrap();
+
+        assert_eq!(
+            vec![
+                ("32T3K".to_string(), 765, 1),
+                ("KTJJT".to_string(), 220, 2),
+                ("KK677".to_string(), 28, 3),
+                ("T55J5".to_string(), 684, 4),
+                ("QQQJA".to_string(), 483, 5),
+            ],
+            standings
+        );
+        assert_eq!(
+            6440,
+            standings
+                .iter()
+                .map(|(_, bid, rank)| *rank as u64 * bid)
+                .sum::<u64>()
+        );
+
+        let input = "AAAAA 1
+23456 3
+AAAAA 2";
+
+        assert_eq!(
+            vec![
+                ("23456".to_string(), 3, 1),
+                ("AAAAA".to_string(), 1, 2),
+                ("AAAAA".to_string(), 2, 3),
+            ],
+            part1::standings(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn winnings_by_type_ok() {
+        let input = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+        let winnings = winnings_by_type(input, false).unwrap();
+
+        assert_eq!(
+            BTreeMap::from([
+                (HandType::OnePair, 765),
+                (HandType::TwoPair, 220 * 2 + 28 * 3),
+                (HandType::ThreeKind, 684 * 4 + 483 * 5),
+            ]),
+            winnings
+        );
+        assert_eq!(6440, winnings.values().sum::<u64>());
+        assert_eq!(
+            5905,
+            winnings_by_type(input, true).unwrap().values().sum::<u64>()
+        );
+    }
 }
@@ -1,135 +1,690 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
-use nom::bytes::complete::{tag, take, take_until1};
+use anyhow::{anyhow, bail, Context, Result};
+use nom::bytes::complete::{tag, take, take_till1};
 use nom::character::complete::{line_ending, multispace1};
+use nom::combinator::not;
 use nom::multi::separated_list1;
-use nom::sequence::{delimited, separated_pair, terminated};
+use nom::sequence::{delimited, separated_pair, terminated, tuple};
 use nom::IResult;
 use num::Integer;
 
-pub mod part1 {
-    use super::*;
+type Network<'a> = HashMap<&'a str, (&'a str, &'a str)>;
 
-    pub fn run(input: &str) -> Result<String> {
-        let (input, instructions) = parse_instruction(input).unwrap();
-        let (_, map) = parse_nodes(input).unwrap();
+/// The shape of a ghost's walk from its starting node.
+#[derive(Debug, PartialEq)]
+pub struct GhostCycle {
+    /// The starting node (ending with "A")
+    pub start: String,
+    /// The number of steps taken to first land on an ending node, None if the
+    /// ghost never does
+    pub first_z: Option<u64>,
+    /// The number of steps after which the walk repeats itself, i.e. the ghost
+    /// is back on the same node at the same point of the instructions
+    pub cycle_len: u64,
+    /// The ending nodes (ending with "Z") visited, in order of first visit
+    pub z_nodes: Vec<String>,
+}
+
+/// Walks every ghost from its starting node until its walk starts repeating,
+/// reporting the shape of each ghost's walk (ordered by starting node). Ghosts
+/// that never land on an ending node are reported too, with no `first_z` and
+/// no `z_nodes`.
+pub fn cycle_report(input: &str) -> Result<Vec<GhostCycle>> {
+    let (instructions, network) = parse(input, false)?;
+
+    let mut starts = network
+        .keys()
+        .filter(|key| key.ends_with('A'))
+        .collect::<Vec<_>>();
+    starts.sort();
+
+    starts
+        .into_iter()
+        .map(|start| ghost_cycle(instructions, &network, start))
+        .collect()
+}
+
+/// Like [`cycle_report`], but written out as a JSON array of objects with
+/// each ghost's `start`, `first_z` and `cycle_len`, e.g.
+/// `[{"start":"11A","first_z":2,"cycle_len":2}]`. A ghost that never lands on
+/// an ending node has a `first_z` of `null`.
+pub fn cycle_report_json(input: &str) -> Result<String> {
+    let ghosts = cycle_report(input)?
+        .iter()
+        .map(|cycle| {
+            format!(
+                "{{\"start\":{},\"first_z\":{},\"cycle_len\":{}}}",
+                json_string(&cycle.start),
+                cycle
+                    .first_z
+                    .map_or("null".to_string(), |steps| steps.to_string()),
+                cycle.cycle_len
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(format!("[{}]", ghosts.join(",")))
+}
+
+/// Quotes `s` as a JSON string, escaping whatever needs it.
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => {
+                json.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+/// Returns the part 2 answer along with what went into it: each ghost's
+/// starting node and the steps it takes to first land on an ending node, which
+/// get LCM-ed together (ordered by starting node).
+///
+/// Errors if a ghost never lands on an ending node, or if the LCM overflows
+/// `u64`.
+pub fn lcm_breakdown(input: &str) -> Result<(u64, Vec<(String, u64)>)> {
+    let steps = cycle_report(input)?
+        .into_iter()
+        .map(|cycle| match cycle.first_z {
+            Some(first_z) => Ok((cycle.start, first_z)),
+            None => {
+                bail!("ghost from {} never reaches an ending node", cycle.start)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let lcm = steps.iter().try_fold(1_u64, |acc, (start, steps)| {
+        (acc / acc.gcd(steps)).checked_mul(*steps).with_context(|| {
+            format!("LCM overflows u64 when adding ghost {}", start)
+        })
+    })?;
+
+    Ok((lcm, steps))
+}
+
+/// Lists the nodes (sorted) whose left and right both lead back to themselves.
+/// Once a walk lands on one of these, it's stuck there for good.
+pub fn sink_nodes(input: &str) -> Result<Vec<String>> {
+    let (_, network) = parse(input, false)?;
 
-        let mut count = 0;
+    let mut sinks = network
+        .iter()
+        .filter(|&(node, &(l, r))| l == *node && r == *node)
+        .map(|(node, _)| node.to_string())
+        .collect::<Vec<_>>();
+    sinks.sort();
+
+    Ok(sinks)
+}
 
-        let mut current = "AAA";
-        for instruction in instructions.chars().cycle() {
-            count += 1;
+/// Splits the network into its strongly connected components: groups of nodes
+/// that can all reach each other by following left and right turns. Nodes are
+/// sorted within each component, and components are sorted by their first
+/// node.
+///
+/// A walk can go from one component to another but never back, so it ends up
+/// going round in the last component it enters.
+pub fn sccs(input: &str) -> Result<Vec<Vec<String>>> {
+    let (_, network) = parse(input, false)?;
 
-            let (l, r) = map.get(current).unwrap();
-            let next = if instruction == 'L' { l } else { r };
+    let mut tarjan = Tarjan {
+        network: &network,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        components: Vec::new(),
+    };
+
+    let mut nodes = network.keys().copied().collect::<Vec<_>>();
+    nodes.sort();
+    for node in nodes {
+        if !tarjan.index.contains_key(node) {
+            tarjan.visit(node)?;
+        }
+    }
 
-            // Stop when we finally reach "ZZZ", otherwise keep going according
-            // to the instruction ("L" or "R").
-            match next {
-                &"ZZZ" => break,
-                next => current = next,
+    let mut components = tarjan.components;
+    for component in components.iter_mut() {
+        component.sort();
+    }
+    components.sort();
+
+    Ok(components)
+}
+
+/// State of Tarjan's strongly connected components algorithm for [`sccs`].
+struct Tarjan<'a> {
+    network: &'a Network<'a>,
+    /// The order in which each node was first visited
+    index: HashMap<&'a str, usize>,
+    /// The lowest index reachable from each node while it's on the stack
+    low: HashMap<&'a str, usize>,
+    /// Visited nodes not yet assigned to a component
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, node: &'a str) -> Result<()> {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.low.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        let &(l, r) = self
+            .network
+            .get(node)
+            .with_context(|| format!("unknown node {}", node))?;
+
+        for next in [l, r] {
+            let reachable = if !self.index.contains_key(next) {
+                self.visit(next)?;
+                self.low[next]
+            } else if self.on_stack.contains(next) {
+                self.index[next]
+            } else {
+                // Already part of an earlier component
+                continue;
             };
+
+            let low = self.low[node].min(reachable);
+            self.low.insert(node, low);
         }
 
-        Ok(count.to_string())
+        // Nothing on the stack above this node can reach further back, so
+        // they make up a component together.
+        if self.low[node] == index {
+            let mut component = Vec::new();
+            while let Some(top) = self.stack.pop() {
+                self.on_stack.remove(top);
+                component.push(top.to_string());
+
+                if top == node {
+                    break;
+                }
+            }
+
+            self.components.push(component);
+        }
+
+        Ok(())
     }
+}
+
+/// Counts the ending nodes (those ending with "Z") in the network.
+pub fn z_node_count(input: &str) -> Result<usize> {
+    let (_, network) = parse(input, false)?;
+
+    Ok(network.keys().filter(|key| key.ends_with('Z')).count())
+}
+
+/// Returns the number of steps the ghost starting at `start` takes to first
+/// land on an ending node, along with the index of the instruction it's at
+/// (i.e. the step count modulo the number of instructions) when it does. None
+/// if the ghost never lands on an ending node.
+pub fn z_phase(input: &str, start: &str) -> Result<Option<(u64, usize)>> {
+    let (instructions, network) = parse(input, false)?;
+    let cycle = ghost_cycle(instructions, &network, start)?;
+
+    Ok(cycle.first_z.map(|first_z| {
+        let phase = first_z % instructions.len() as u64;
+        (first_z, phase as usize)
+    }))
+}
+
+/// Returns every node visited by following the instructions from `start`
+/// (itself included), up to the point where the walk starts repeating itself.
+pub fn reachable_nodes(input: &str, start: &str) -> Result<HashSet<String>> {
+    let (instructions, network) = parse(input, false)?;
+    let path = path_until_repeat(instructions, &network, start)?;
+
+    Ok(path.into_iter().map(str::to_string).collect())
+}
+
+/// Counts the steps it takes to first get from `start` to `target`, None if
+/// the walk starts repeating itself before ever getting there.
+///
+/// Like [`steps_to`], the starting node itself doesn't count, so
+/// `start == target` needs the walk to come back around to it.
+pub fn first_reach(
+    input: &str,
+    start: &str,
+    target: &str,
+) -> Result<Option<u64>> {
+    let (instructions, network) = parse(input, false)?;
 
-    /// Parses the instruction string (e.g. `LRL`).
-    fn parse_instruction(input: &str) -> IResult<&str, &str> {
-        terminated(take_until1("\n"), multispace1)(input)
+    walk(instructions, &network, start, 0, |node| node == target)
+}
+
+/// Lists the nodes visited by following the instructions from `start`, in
+/// order and starting with `start` itself, until the ghost is back on the same
+/// node at the same point of the instructions (that node being the last one
+/// listed). Past that, the walk can only repeat itself.
+fn path_until_repeat<'a>(
+    instructions: &str,
+    network: &Network<'a>,
+    start: &'a str,
+) -> Result<Vec<&'a str>> {
+    if !network.contains_key(start) {
+        bail!("start node {} not found", start);
     }
+    let instructions = instructions.chars().collect::<Vec<_>>();
 
-    /// Parses the map of each node to its left and right destinations.
-    fn parse_nodes(input: &str) -> IResult<&str, HashMap<&str, (&str, &str)>> {
-        let (input, maps) = separated_list1(
-            line_ending,
-            separated_pair(
-                take(3usize),
-                tag(" = "),
-                delimited(
-                    tag("("),
-                    separated_pair(take(3usize), tag(", "), take(3usize)),
-                    tag(")"),
-                ),
-            ),
-        )(input)?;
+    // Every (node, instruction index) state seen so far
+    let mut seen = HashSet::new();
+    let mut path = vec![start];
+    let mut current = start;
+    for &instruction in instructions.iter().cycle() {
+        let index = seen.len() % instructions.len();
+        if !seen.insert((current, index)) {
+            break;
+        }
 
-        Ok((input, HashMap::from_iter(maps)))
+        current = turn(network, current, instruction)?;
+        path.push(current);
     }
+
+    Ok(path)
 }
 
-pub mod part2 {
-    use super::*;
+/// Walks from `start` until the same node is reached at the same instruction
+/// index again, after which the walk can only repeat itself.
+fn ghost_cycle(
+    instructions: &str,
+    network: &Network,
+    start: &str,
+) -> Result<GhostCycle> {
+    let instructions = instructions.chars().collect::<Vec<_>>();
 
-    pub fn run(input: &str) -> Result<String> {
-        let (input, instructions) = parse_instruction(input).unwrap();
-        let (_, map) = parse_nodes(input).unwrap();
-
-        // Starting nodes are those ending with "A"
-        let mut paths = map
-            .keys()
-            .filter(|key| key.ends_with('A'))
-            .collect::<Vec<_>>();
-
-        // This is a counter for the number of steps needed for EACH starting
-        // node to reach its ending node (node ending with "Z").
-        let mut steps = paths.iter().map(|_| 0).collect::<Vec<u64>>();
-
-        for instruction in instructions.chars().cycle() {
-            // Only stop when all nodes are ending nodes
-            if paths.iter().all(|path| path.ends_with('Z')) {
-                break;
-            }
+    // The step at which each (node, instruction index) state was first seen
+    let mut seen: HashMap<(&str, usize), u64> = HashMap::new();
+    let mut first_z = None;
+    let mut z_nodes = Vec::new();
 
-            for (path, step) in paths.iter_mut().zip(steps.iter_mut()) {
-                // This particular node has reached its ending node, we can skip
-                if path.ends_with('Z') {
-                    continue;
-                }
+    let mut current = start;
+    let mut step = 0;
+    loop {
+        let index = step as usize % instructions.len();
+        if let Some(seen_step) = seen.insert((current, index), step) {
+            return Ok(GhostCycle {
+                start: start.to_string(),
+                first_z,
+                cycle_len: step - seen_step,
+                z_nodes,
+            });
+        }
 
-                // Keep upping the counter for this node until we find its
-                // ending node.
-                let (l, r) = map.get(path.to_owned()).unwrap();
-                *path = if instruction == 'L' { l } else { r };
-                *step += 1;
+        if current.ends_with('Z') {
+            first_z.get_or_insert(step);
+            if !z_nodes.contains(&current.to_string()) {
+                z_nodes.push(current.to_string());
             }
         }
 
-        // Apparently, the answer is achieved by LCM-ing all starting nodes'
-        // number of steps to reach their own respective ending nodes? I never
-        // would've guessed this — all credit goes to the comments at the AoC
-        // subreddit (although they also seem baffled by how LCM turns out to
-        // lead to the answer).
-        let total = steps
-            .into_iter()
-            .reduce(|acc, step| acc.lcm(&step))
-            .unwrap();
+        current = turn(network, current, instructions[index])?;
+        step += 1;
+    }
+}
 
-        Ok(total.to_string())
+/// Counts the steps it takes to get from `start` to `target`, following the
+/// instructions from the beginning.
+///
+/// A step is one edge traversed, so the starting node itself doesn't count:
+/// `AAA -> CCC -> ZZZ` is 2 steps. Arriving back at `start` counts, though,
+/// so `start == target` needs at least one full trip around.
+pub fn steps_to(input: &str, start: &str, target: &str) -> Result<u64> {
+    let (instructions, network) = parse(input, false)?;
+    if !network.contains_key(target) {
+        bail!("no {} node in graph", target);
     }
 
-    /// Parses the instruction string (e.g. `LRL`).
-    fn parse_instruction(input: &str) -> IResult<&str, &str> {
-        terminated(take_until1("\n"), multispace1)(input)
+    walk(instructions, &network, start, 0, |node| node == target)?
+        .with_context(|| format!("{} is never reached from {}", target, start))
+}
+
+/// Counts the steps it takes to get from `from` to the first node matching
+/// `to_pred`, starting at the instruction at index `offset` (so a walk can pick
+/// up where an earlier one left off). None if no such node is ever reached.
+///
+/// Like [`steps_to`], at least one step is always taken, so e.g. the length of
+/// the cycle from an ending node back to an ending node can be measured.
+pub fn steps_between(
+    input: &str,
+    from: &str,
+    offset: usize,
+    to_pred: impl Fn(&str) -> bool,
+) -> Result<Option<u64>> {
+    let (instructions, network) = parse(input, false)?;
+
+    walk(instructions, &network, from, offset, to_pred)
+}
+
+/// Does the walk for [`steps_between`].
+fn walk(
+    instructions: &str,
+    network: &Network,
+    from: &str,
+    offset: usize,
+    to_pred: impl Fn(&str) -> bool,
+) -> Result<Option<u64>> {
+    if !network.contains_key(from) {
+        bail!("start node {} not found", from);
+    }
+    if offset >= instructions.len() {
+        bail!(
+            "instruction offset {} is out of range for {} instructions",
+            offset,
+            instructions.len()
+        );
     }
 
-    /// Parses the map of each node to its left and right destinations.
-    fn parse_nodes(input: &str) -> IResult<&str, HashMap<&str, (&str, &str)>> {
-        let (input, maps) = separated_list1(
-            line_ending,
-            separated_pair(
-                take(3usize),
-                tag(" = "),
-                delimited(
-                    tag("("),
-                    separated_pair(take(3usize), tag(", "), take(3usize)),
-                    tag(")"),
-                ),
+    // After this many steps without a match, some (node, instruction) state
+    // must have repeated, so we're walking in circles.
+    let limit = (network.len() * instructions.len()) as u64;
+
+    let mut current = from;
+    let instructions = instructions.chars().cycle().skip(offset);
+    for (steps, instruction) in (1..=limit).zip(instructions) {
+        current = turn(network, current, instruction)?;
+
+        if to_pred(current) {
+            return Ok(Some(steps));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Follows a single instruction (`L` or `R`) from `node`, returning the node
+/// it leads to.
+fn turn<'a>(
+    network: &Network<'a>,
+    node: &str,
+    instruction: char,
+) -> Result<&'a str> {
+    let &(l, r) = network
+        .get(node)
+        .with_context(|| format!("unknown node {}", node))?;
+
+    match instruction {
+        'L' => Ok(l),
+        'R' => Ok(r),
+        other => bail!("invalid instruction {:?}", other),
+    }
+}
+
+/// Counts the steps part 2 takes by moving every ghost (one per node ending
+/// with "A") together until they all stand on nodes ending with "Z" at once.
+///
+/// Unlike `part2::run`, this doesn't rely on the LCM shortcut, so it can be
+/// used to check it on small graphs. Errors if it takes more than `max_steps`.
+pub fn run_part2_simulated(input: &str, max_steps: u64) -> Result<u64> {
+    let (instructions, network) = parse(input, false)?;
+
+    let mut ghosts = network
+        .keys()
+        .copied()
+        .filter(|node| node.ends_with('A'))
+        .collect::<Vec<_>>();
+    if ghosts.is_empty() {
+        bail!("no starting nodes (ending with \"A\") in graph");
+    }
+
+    let instructions = instructions.chars().cycle();
+    for (steps, instruction) in (1..=max_steps).zip(instructions) {
+        for ghost in ghosts.iter_mut() {
+            *ghost = turn(&network, ghost, instruction)?;
+        }
+
+        if ghosts.iter().all(|ghost| ghost.ends_with('Z')) {
+            return Ok(steps);
+        }
+    }
+
+    bail!(
+        "ghosts aren't all on ending nodes after {} steps",
+        max_steps
+    )
+}
+
+/// Counts the nodes visited on the way from `start` to `target`, both ends
+/// included. This is always one more than [`steps_to`].
+pub fn node_count_visited(
+    input: &str,
+    start: &str,
+    target: &str,
+) -> Result<u64> {
+    Ok(steps_to(input, start, target)? + 1)
+}
+
+/// A run of consecutive identical instructions, e.g. `"LLL"` -> `('L', 3)`.
+type Run = (char, usize);
+
+/// Counts the steps it takes to get from `start` to `target`, like part 1 but
+/// jumping over whole runs of identical instructions like `LLLLRRRR` at a
+/// time.
+///
+/// The jumps come from a [`JumpTable`], which only works out the ones the
+/// walk actually needs. The walk is going in circles once it starts the same
+/// run on the same node twice.
+pub fn steps_compressed(input: &str, start: &str, target: &str) -> Result<u64> {
+    let (instructions, network) = parse(input, false)?;
+    if !network.contains_key(start) {
+        bail!("start node {} not found", start);
+    }
+    if !network.contains_key(target) {
+        bail!("no {} node in graph", target);
+    }
+
+    let mut table = JumpTable::new(&network, target);
+    compressed_walk(&mut table, &runs(instructions), start)
+}
+
+/// Does the walk for [`steps_compressed`], one run at a time.
+fn compressed_walk<'a>(
+    table: &mut JumpTable<'a, '_>,
+    runs: &[Run],
+    start: &'a str,
+) -> Result<u64> {
+    // Every (node, run index) state seen so far
+    let mut seen = HashSet::new();
+
+    let mut steps = 0;
+    let mut current = start;
+    for (index, &run) in runs.iter().enumerate().cycle() {
+        if !seen.insert((current, index)) {
+            bail!("{} is never reached from {}", table.target, start);
+        }
+
+        let (next, hit) = table.jump(current, run)?;
+        if let Some(offset) = hit {
+            return Ok(steps + offset);
+        }
+
+        steps += run.1 as u64;
+        current = next;
+    }
+
+    bail!("no instructions to follow")
+}
+
+/// Splits the instruction string into runs of identical instructions.
+///
+/// `"LLRL"` -> `[('L', 2), ('R', 1), ('L', 1)]`
+fn runs(instructions: &str) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+
+    for instruction in instructions.chars() {
+        match runs.last_mut() {
+            Some((direction, len)) if *direction == instruction => *len += 1,
+            _ => runs.push((instruction, 1)),
+        }
+    }
+
+    runs
+}
+
+/// Lazily memoised jumps over runs of identical instructions, keyed by the
+/// node a run starts on and the run itself.
+///
+/// A run longer than one step is split at the largest power of two below its
+/// length, and the two halves are jumped over in turn. The halves get shared
+/// between runs of different lengths, so only single steps ever follow the
+/// network node by node, and each of those at most once per node.
+struct JumpTable<'a, 'n> {
+    network: &'n Network<'a>,
+    target: &'n str,
+    /// (node, run) -> (node the run ends on, step (1-based) within the run at
+    /// which `target` is first reached, if it is)
+    jumps: HashMap<(&'a str, Run), (&'a str, Option<u64>)>,
+    /// The number of jumps answered straight from the table
+    hits: usize,
+}
+
+impl<'a, 'n> JumpTable<'a, 'n> {
+    fn new(network: &'n Network<'a>, target: &'n str) -> Self {
+        Self {
+            network,
+            target,
+            jumps: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Jumps over `run` from `node`, returning the node it ends on and the
+    /// step (1-based) within the run at which `target` is first reached, if
+    /// it is. The run stops early at `target`, so past it nothing is followed
+    /// (and the node returned is `target` itself).
+    fn jump(
+        &mut self,
+        node: &'a str,
+        run: Run,
+    ) -> Result<(&'a str, Option<u64>)> {
+        if let Some(&jump) = self.jumps.get(&(node, run)) {
+            self.hits += 1;
+            return Ok(jump);
+        }
+
+        let (direction, len) = run;
+        let jump = if len <= 1 {
+            let next = turn(self.network, node, direction)?;
+            (next, (next == self.target).then_some(1))
+        } else {
+            let head = 1 << (len - 1).ilog2();
+            match self.jump(node, (direction, head))? {
+                hit @ (_, Some(_)) => hit,
+                (middle, None) => {
+                    let (end, hit) =
+                        self.jump(middle, (direction, len - head))?;
+                    (end, hit.map(|step| head as u64 + step))
+                }
+            }
+        };
+        self.jumps.insert((node, run), jump);
+
+        Ok(jump)
+    }
+}
+
+/// Checks that the input is laid out exactly as expected: the instruction
+/// string, exactly one blank line, then the nodes.
+pub fn validate(input: &str) -> Result<()> {
+    parse(input, true).map(|_| ())
+}
+
+/// Parses the instruction string and the node network.
+///
+/// In strict mode, exactly one blank line must separate the instructions from
+/// the nodes. Otherwise any amount of whitespace is accepted.
+fn parse(input: &str, strict: bool) -> Result<(&str, Network<'_>)> {
+    let (input, instructions) = if strict {
+        parse_instruction_strict(input).map_err(|e| {
+            anyhow!("expected exactly one blank line after instructions: {e}")
+        })?
+    } else {
+        parse_instruction(input)
+            .map_err(|e| anyhow!("failed to parse instructions: {e}"))?
+    };
+    let (_, network) = parse_nodes(input)
+        .map_err(|e| anyhow!("failed to parse nodes: {e}"))?;
+
+    Ok((instructions, network))
+}
+
+/// Parses the instruction string (e.g. `LRL`), which can't be empty.
+fn parse_instruction(input: &str) -> IResult<&str, &str> {
+    terminated(instruction_line, multispace1)(input)
+}
+
+/// Parses the instruction string (e.g. `LRL`), which must be followed by
+/// exactly one blank line.
+fn parse_instruction_strict(input: &str) -> IResult<&str, &str> {
+    terminated(
+        instruction_line,
+        tuple((line_ending, line_ending, not(multispace1))),
+    )(input)
+}
+
+/// Parses the non-empty contents of the instruction line.
+fn instruction_line(input: &str) -> IResult<&str, &str> {
+    take_till1(|c| c == '\r' || c == '\n')(input)
+}
+
+/// Parses the map of each node to its left and right destinations.
+fn parse_nodes(input: &str) -> IResult<&str, HashMap<&str, (&str, &str)>> {
+    let (input, maps) = separated_list1(
+        line_ending,
+        separated_pair(
+            take(3usize),
+            tag(" = "),
+            delimited(
+                tag("("),
+                separated_pair(take(3usize), tag(", "), take(3usize)),
+                tag(")"),
             ),
-        )(input)?;
+        ),
+    )(input)?;
 
-        Ok((input, HashMap::from_iter(maps)))
+    Ok((input, HashMap::from_iter(maps)))
+}
+
+pub mod part1 {
+    use super::*;
+
+    pub fn run(input: &str) -> Result<String> {
+        let count = steps_to(input, "AAA", "ZZZ")?;
+
+        Ok(count.to_string())
+    }
+}
+
+pub mod part2 {
+    use super::*;
+
+    pub fn run(input: &str) -> Result<String> {
+        // Apparently, the answer is achieved by LCM-ing all starting nodes'
+        // number of steps to reach their own respective ending nodes? I never
+        // would've guessed this — all credit goes to the comments at the AoC
+        // subreddit (although they also seem baffled by how LCM turns out to
+        // lead to the answer).
+        let (total, steps) = lcm_breakdown(input)?;
+        if steps.is_empty() {
+            bail!("no starting nodes (ending with \"A\") in graph");
+        }
+
+        Ok(total.to_string())
     }
 }
 
@@ -175,4 +730,455 @@ XXX = (XXX, XXX)";
 
         assert_eq!("6", part2::run(input).unwrap());
     }
+
+    #[test]
+    fn cycle_report_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        let report = cycle_report(input).unwrap();
+
+        assert_eq!(
+            vec![
+                GhostCycle {
+                    start: "11A".to_string(),
+                    first_z: Some(2),
+                    cycle_len: 2,
+                    z_nodes: vec!["11Z".to_string()],
+                },
+                GhostCycle {
+                    start: "22A".to_string(),
+                    first_z: Some(3),
+                    cycle_len: 6,
+                    z_nodes: vec!["22Z".to_string()],
+                },
+            ],
+            report
+        );
+    }
+
+    #[test]
+    fn ghost_never_reaching_z() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (XXX, XXX)
+XXX = (XXX, XXX)";
+
+        assert_eq!(
+            GhostCycle {
+                start: "22A".to_string(),
+                first_z: None,
+                cycle_len: 2,
+                z_nodes: vec![],
+            },
+            cycle_report(input).unwrap().remove(1)
+        );
+        assert_eq!(
+            r#"[{"start":"11A","first_z":2,"cycle_len":2},{"start":"22A","first_z":null,"cycle_len":2}]"#,
+            cycle_report_json(input).unwrap()
+        );
+        assert_eq!(None, z_phase(input, "22A").unwrap());
+        assert!(lcm_breakdown(input).is_err());
+        assert_eq!(
+            "ghost from 22A never reaches an ending node",
+            part2::run(input).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn validate_blank_line() {
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+        assert!(validate(input).is_ok());
+
+        let input = "LLR
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+        assert!(validate(input).is_err());
+
+        let input = "LLR
+
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+        assert!(validate(input).is_err());
+        assert_eq!("6", part1::run(input).unwrap());
+    }
+
+    #[test]
+    fn z_phase_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(Some((2, 0)), z_phase(input, "11A").unwrap());
+        assert_eq!(Some((3, 1)), z_phase(input, "22A").unwrap());
+    }
+
+    #[test]
+    fn steps_compressed_ok() {
+        let input = "LLLL
+
+AAA = (BBB, XXX)
+BBB = (CCC, XXX)
+CCC = (DDD, XXX)
+DDD = (EEE, XXX)
+EEE = (ZZZ, XXX)
+ZZZ = (ZZZ, ZZZ)
+XXX = (XXX, XXX)";
+
+        assert_eq!("5", part1::run(input).unwrap());
+        assert_eq!(5, steps_compressed(input, "AAA", "ZZZ").unwrap());
+
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!("6", part1::run(input).unwrap());
+        assert_eq!(6, steps_compressed(input, "AAA", "ZZZ").unwrap());
+    }
+
+    #[test]
+    fn steps_compressed_long_run_ok() {
+        let input = format!(
+            "{}R
+
+AAA = (BBB, ZZZ)
+BBB = (CCC, ZZZ)
+CCC = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)
+QQQ = (NOPE, NOPE)",
+            "L".repeat(64)
+        );
+
+        assert_eq!("65", part1::run(&input).unwrap());
+        assert_eq!(65, steps_compressed(&input, "AAA", "ZZZ").unwrap());
+        assert!(steps_compressed(&input, "AAA", "YYY").is_err());
+
+        // The 64 L's are split into halves of halves, and going round just 3
+        // nodes, most of those have been jumped over before.
+        let (instructions, network) = parse(&input, false).unwrap();
+        let mut table = JumpTable::new(&network, "ZZZ");
+        let steps = compressed_walk(&mut table, &runs(instructions), "AAA");
+
+        assert_eq!(65, steps.unwrap());
+        assert!(table.hits > 0);
+        assert!(table.jumps.len() <= 3 * 7 + 1);
+    }
+
+    #[test]
+    fn invalid_instruction_err() {
+        let input = "LX
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert!(steps_to(input, "AAA", "ZZZ").is_err());
+        assert!(steps_compressed(input, "AAA", "ZZZ").is_err());
+    }
+
+    #[test]
+    fn crlf_ok() {
+        let input = "LLR\r
+\r
+AAA = (BBB, BBB)\r
+BBB = (AAA, ZZZ)\r
+ZZZ = (ZZZ, ZZZ)\r
+";
+        assert_eq!("6", part1::run(input).unwrap());
+        assert!(validate(input).is_ok());
+
+        let input = "LR\r
+\r
+11A = (11B, XXX)\r
+11B = (XXX, 11Z)\r
+11Z = (11B, XXX)\r
+22A = (22B, XXX)\r
+22B = (22C, 22C)\r
+22C = (22Z, 22Z)\r
+22Z = (22B, 22B)\r
+XXX = (XXX, XXX)\r
+";
+        assert_eq!("6", part2::run(input).unwrap());
+    }
+
+    #[test]
+    fn steps_to_ok() {
+        let input = "RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+
+        // AAA -> CCC -> ZZZ
+        assert_eq!(2, steps_to(input, "AAA", "ZZZ").unwrap());
+        assert_eq!(3, node_count_visited(input, "AAA", "ZZZ").unwrap());
+        assert!(steps_to(input, "AAA", "DDD").is_err());
+
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(6, steps_to(input, "AAA", "ZZZ").unwrap());
+        assert_eq!(7, node_count_visited(input, "AAA", "ZZZ").unwrap());
+    }
+
+    #[test]
+    fn z_node_count_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(2, z_node_count(input).unwrap());
+    }
+
+    #[test]
+    fn lcm_breakdown_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(
+            (6, vec![("11A".to_string(), 2), ("22A".to_string(), 3)]),
+            lcm_breakdown(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn sink_nodes_ok() {
+        let input = "RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(
+            vec!["DDD", "EEE", "GGG", "ZZZ"],
+            sink_nodes(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn steps_between_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+        let is_end = |node: &str| node.ends_with('Z');
+
+        // 11A first lands on 11Z after 2 steps, i.e. back at instruction 0
+        assert_eq!(Some(2), steps_between(input, "11A", 0, is_end).unwrap());
+        assert_eq!(Some(2), steps_between(input, "11Z", 0, is_end).unwrap());
+
+        // 22A first lands on 22Z after 3 steps, i.e. at instruction 1
+        assert_eq!(Some(3), steps_between(input, "22A", 0, is_end).unwrap());
+        assert_eq!(Some(3), steps_between(input, "22Z", 1, is_end).unwrap());
+
+        assert_eq!(None, steps_between(input, "XXX", 0, is_end).unwrap());
+        assert!(steps_between(input, "11A", 2, is_end).is_err());
+    }
+
+    #[test]
+    fn empty_instructions_err() {
+        let input = "\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)\n";
+
+        assert!(part1::run(input).is_err());
+        assert!(part2::run(input).is_err());
+        assert!(validate(input).is_err());
+        assert!(cycle_report(input).is_err());
+        assert!(steps_compressed(input, "AAA", "ZZZ").is_err());
+    }
+
+    #[test]
+    fn part1_missing_node_err() {
+        let input = "LLR
+
+BBB = (BBB, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+        assert_eq!(
+            "start node AAA not found",
+            part1::run(input).unwrap_err().to_string()
+        );
+
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, AAA)";
+        assert_eq!(
+            "no ZZZ node in graph",
+            part1::run(input).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn run_part2_simulated_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(6, run_part2_simulated(input, 100).unwrap());
+        assert!(run_part2_simulated(input, 5).is_err());
+    }
+
+    #[test]
+    fn sccs_ok() {
+        let input = "RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(
+            vec![
+                vec!["AAA"],
+                vec!["BBB"],
+                vec!["CCC"],
+                vec!["DDD"],
+                vec!["EEE"],
+                vec!["GGG"],
+                vec!["ZZZ"],
+            ],
+            sccs(input).unwrap()
+        );
+
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(vec![vec!["AAA", "BBB"], vec!["ZZZ"]], sccs(input).unwrap());
+    }
+
+    #[test]
+    fn cycle_report_json_ok() {
+        let input = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(
+            r#"[{"start":"11A","first_z":2,"cycle_len":2},{"start":"22A","first_z":3,"cycle_len":6}]"#,
+            cycle_report_json(input).unwrap()
+        );
+        assert_eq!(r#""a\"b\\c\u000a""#, json_string("a\"b\\c\n"));
+    }
+
+    #[test]
+    fn reachable_nodes_ok() {
+        let input = "RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(
+            HashSet::from(["AAA", "CCC", "ZZZ"].map(String::from)),
+            reachable_nodes(input, "AAA").unwrap()
+        );
+        assert!(reachable_nodes(input, "XXX").is_err());
+    }
+
+    #[test]
+    fn first_reach_ok() {
+        let input = "RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(Some(1), first_reach(input, "AAA", "CCC").unwrap());
+        assert_eq!(Some(2), first_reach(input, "AAA", "ZZZ").unwrap());
+        assert_eq!(None, first_reach(input, "AAA", "BBB").unwrap());
+        assert_eq!(None, first_reach(input, "AAA", "AAA").unwrap());
+
+        let input = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+
+        assert_eq!(Some(2), first_reach(input, "AAA", "AAA").unwrap());
+    }
 }